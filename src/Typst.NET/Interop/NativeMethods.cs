@@ -76,6 +76,14 @@ internal static partial class NativeMethods
 
     [LibraryImport(LibraryName)]
     internal static unsafe partial nuint typst_net_version_len();
+
+    /// <summary>
+    /// ABI version these declarations were written for (TYPST_NET_ABI_VERSION in typst_net.h)
+    /// </summary>
+    internal const uint AbiVersion = 1;
+
+    [LibraryImport(LibraryName)]
+    internal static partial uint typst_net_abi_version();
     #endregion
 
     #region COMPILER LIFECYCLE
@@ -123,6 +131,22 @@ internal static partial class NativeMethods
         nuint sourceLen
     );
 
+    /// <summary>
+    /// Compile typst source code, aborting once a cancellation token is triggered.
+    /// </summary>
+    /// <param name="compiler">Valid compiler handle</param>
+    /// <param name="source">UTF-8 encoded source code</param>
+    /// <param name="sourceLen">Length of source in bytes</param>
+    /// <param name="cancelToken">Token from typst_net_cancel_token_create (can be null)</param>
+    /// <returns>Compilation result. Must be freed with typst_net_result_free</returns>
+    [LibraryImport(LibraryName)]
+    internal static unsafe partial CompileResult typst_net_compiler_compile_with_cancel(
+        nint compiler,
+        byte* source,
+        nuint sourceLen,
+        nint cancelToken
+    );
+
     /// <summary>
     /// Free a compilation result.
     /// </summary>
@@ -130,6 +154,36 @@ internal static partial class NativeMethods
     internal static partial void typst_net_result_free(CompileResult result);
     #endregion
 
+    #region CANCELLATION
+    // ========================================================================
+    // CANCELLATION
+    // ========================================================================
+
+    /// <summary>
+    /// Create a cancellation token. Must be freed with typst_net_cancel_token_free.
+    /// </summary>
+    [LibraryImport(LibraryName)]
+    internal static partial nint typst_net_cancel_token_create();
+
+    /// <summary>
+    /// Trigger a cancellation token (safe from any thread).
+    /// </summary>
+    [LibraryImport(LibraryName)]
+    internal static partial void typst_net_cancel_token_cancel(nint token);
+
+    /// <summary>
+    /// Free a cancellation token. Operations still using it keep their own reference.
+    /// </summary>
+    [LibraryImport(LibraryName)]
+    internal static partial void typst_net_cancel_token_free(nint token);
+
+    /// <summary>
+    /// Number of abandoned (timed out or canceled) compilations still running natively.
+    /// </summary>
+    [LibraryImport(LibraryName)]
+    internal static partial nuint typst_net_abandoned_compiles();
+    #endregion
+
     #region DOCUMENT OPERATIONS
     // ========================================================================
     // DOCUMENT OPERATIONS
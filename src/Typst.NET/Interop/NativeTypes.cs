@@ -35,6 +35,20 @@ internal enum DiagnosticSeverity : byte
     Hint = 2,
 }
 
+/// <summary>
+/// Failure reason matching Rust ErrorKind enum.
+/// repr(u8) in Rust corresponds to byte in C#.
+/// </summary>
+internal enum ErrorKind : byte
+{
+    None = 0,
+    Failed = 1,
+    TimeLimitExceeded = 2,
+    PageLimitExceeded = 3,
+    OutputLimitExceeded = 4,
+    Cancelled = 5,
+}
+
 /// <summary>
 /// Source location for diagnostics (1-indexed).
 /// </summary>
@@ -68,6 +82,18 @@ internal unsafe struct CompileResult
     public Diagnostic* Diagnostics;
     public nuint DiagnosticsLength;
     public void* Document;
+    public ErrorKind ErrorKind;
+}
+
+/// <summary>
+/// Array of compile results allocated by Rust (batch compilation).
+/// Must be freed with typst_net_result_array_free.
+/// </summary>
+[StructLayout(LayoutKind.Sequential)]
+internal unsafe struct CompileResultArray
+{
+    public CompileResult* Results;
+    public nuint Length;
 }
 
 /// <summary>
@@ -77,6 +103,12 @@ internal unsafe struct CompileResult
 [StructLayout(LayoutKind.Sequential)]
 internal struct CompilerOptions
 {
+    /// <summary>
+    /// sizeof(CompilerOptions) as this package knows it, so a newer native
+    /// library leaves fields added after it at their defaults
+    /// </summary>
+    public nuint StructSize;
+
     /// <summary>
     /// Include system fonts (default: true)
     /// </summary>
@@ -100,4 +132,94 @@ internal struct CompilerOptions
     /// </summary>
     public unsafe byte* PackagePath;
     public nuint PackagePathLength;
+
+    /// <summary>
+    /// Sandbox: allowed file extensions as JSON array (null allows all)
+    /// </summary>
+    public unsafe byte* AllowedExtensions;
+    public nuint AllowedExtensionsLength;
+
+    /// <summary>
+    /// Sandbox: maximum size of a single file in bytes (0 = unlimited)
+    /// </summary>
+    public ulong MaxFileSize;
+
+    /// <summary>
+    /// Sandbox: maximum bytes read per compilation (0 = unlimited)
+    /// </summary>
+    public ulong MaxTotalReadBytes;
+
+    /// <summary>
+    /// Sandbox: deny all filesystem access
+    /// </summary>
+    [MarshalAs(UnmanagedType.U1)]
+    public bool ForbidFilesystem;
+
+    /// <summary>
+    /// Limit: maximum compile time in milliseconds (0 = unlimited). The call returns at the
+    /// limit, but the CPU is not reclaimed until the native compilation finishes.
+    /// </summary>
+    public ulong MaxCompileTimeMs;
+
+    /// <summary>
+    /// Limit: maximum page count (0 = unlimited)
+    /// </summary>
+    public ulong MaxPages;
+
+    /// <summary>
+    /// Limit: maximum render output size in bytes (0 = unlimited)
+    /// </summary>
+    public ulong MaxOutputBytes;
+
+    /// <summary>
+    /// Use FixedDateUnixSeconds instead of the clock (SOURCE_DATE_EPOCH is honored otherwise)
+    /// </summary>
+    [MarshalAs(UnmanagedType.U1)]
+    public bool UseFixedDate;
+
+    /// <summary>
+    /// Fixed date as Unix timestamp in seconds
+    /// </summary>
+    public long FixedDateUnixSeconds;
+
+    /// <summary>
+    /// Threads used to render pages in parallel (0 = one per CPU core)
+    /// </summary>
+    public uint RenderThreads;
+
+    /// <summary>
+    /// Include the fonts bundled with the native library
+    /// </summary>
+    [MarshalAs(UnmanagedType.U1)]
+    public bool IncludeEmbeddedFonts;
+
+    /// <summary>
+    /// Cache directory for downloaded packages (null disables downloading)
+    /// </summary>
+    public unsafe byte* PackageCachePath;
+    public nuint PackageCachePathLength;
+
+    /// <summary>
+    /// Registry base URL packages are downloaded from (null = https://packages.typst.org)
+    /// </summary>
+    public unsafe byte* PackageRegistryUrl;
+    public nuint PackageRegistryUrlLength;
+
+    /// <summary>
+    /// Only use packages already in the cache, never download
+    /// </summary>
+    [MarshalAs(UnmanagedType.U1)]
+    public bool OfflinePackages;
+
+    /// <summary>
+    /// More package roots searched after PackagePath, as JSON array
+    /// </summary>
+    public unsafe byte* PackagePaths;
+    public nuint PackagePathsLength;
+
+    /// <summary>
+    /// Search the user package directory (where @local packages live) last
+    /// </summary>
+    [MarshalAs(UnmanagedType.U1)]
+    public bool IncludeUserPackages;
 }
@@ -33,6 +33,12 @@ public sealed class TypstCompiler : IDisposable
                 nameof(options)
             );
 
+        var abiVersion = NativeMethods.typst_net_abi_version();
+        if (abiVersion != NativeMethods.AbiVersion)
+            throw new TypstException(
+                $"Native library ABI version {abiVersion} does not match the expected version {NativeMethods.AbiVersion}"
+            );
+
         var rootBytes = InteropHelpers.StringToUtf8Bytes(options.WorkspaceRoot);
 
         var inputBytes =
@@ -60,6 +66,7 @@ public sealed class TypstCompiler : IDisposable
                 {
                     var nativeOptions = new CompilerOptions
                     {
+                        StructSize = (nuint)sizeof(CompilerOptions),
                         IncludeSystemFonts = options.IncludeSystemFonts,
                         InputsJson = inputsPtr,
                         InputsJsonLength = (nuint)inputBytes.Length,
@@ -67,6 +74,7 @@ public sealed class TypstCompiler : IDisposable
                         CustomFontPathsLength = (nuint)fontPathBytes.Length,
                         PackagePath = pkgPtr,
                         PackagePathLength = (nuint)packageBytes.Length,
+                        IncludeEmbeddedFonts = true,
                         // Add more options here as needed
                     };
 
@@ -118,10 +126,26 @@ public sealed class TypstCompiler : IDisposable
     /// <returns>A <see cref="CompileResult"/> containing the compiled document and any diagnostics.</returns>
     /// <exception cref="ArgumentNullException">Thrown if <paramref name="source"/> is null.</exception>
     /// <exception cref="ObjectDisposedException">Thrown if the compiler instance has been disposed.</exception>
-    public CompileResult Compile(string source)
+    public CompileResult Compile(string source) => Compile(source, CancellationToken.None);
+
+    /// <summary>
+    /// Compile typst source code into a document result, stopping once <paramref name="cancellationToken"/> is canceled.
+    /// </summary>
+    /// <param name="source">Typst markup source code.</param>
+    /// <param name="cancellationToken">Token that aborts the compilation. The call returns promptly once it is canceled.</param>
+    /// <remarks>
+    /// Cancellation releases the caller, not the CPU: the native compiler can't be interrupted, so a canceled
+    /// compilation keeps running on a native thread until it finishes. See <see cref="AbandonedCompilations"/>.
+    /// </remarks>
+    /// <returns>A <see cref="CompileResult"/> containing the compiled document and any diagnostics.</returns>
+    /// <exception cref="ArgumentNullException">Thrown if <paramref name="source"/> is null.</exception>
+    /// <exception cref="ObjectDisposedException">Thrown if the compiler instance has been disposed.</exception>
+    /// <exception cref="OperationCanceledException">Thrown if <paramref name="cancellationToken"/> was canceled.</exception>
+    public CompileResult Compile(string source, CancellationToken cancellationToken)
     {
         ArgumentNullException.ThrowIfNull(source);
         ObjectDisposedException.ThrowIf(_disposed, this);
+        cancellationToken.ThrowIfCancellationRequested();
 
         var byteCount = System.Text.Encoding.UTF8.GetByteCount(source);
 
@@ -141,31 +165,74 @@ public sealed class TypstCompiler : IDisposable
             buffer = rentedArray.AsSpan(0, byteCount);
         }
 
+        // Native token triggered by the managed one; null when it can't be canceled
+        var cancelToken = cancellationToken.CanBeCanceled
+            ? NativeMethods.typst_net_cancel_token_create()
+            : 0;
+
         try
         {
             System.Text.Encoding.UTF8.GetBytes(source, buffer);
 
+            NET.Interop.CompileResult result;
             unsafe
             {
                 fixed (byte* sourcePtr = buffer)
                 {
-                    var result = NativeMethods.typst_net_compiler_compile(
-                        _handle!.DangerousGetHandle(),
-                        sourcePtr,
-                        (nuint)byteCount
-                    );
+                    if (cancelToken == 0)
+                    {
+                        result = NativeMethods.typst_net_compiler_compile(
+                            _handle!.DangerousGetHandle(),
+                            sourcePtr,
+                            (nuint)byteCount
+                        );
+                    }
+                    else
+                    {
+                        // Disposed before the token is freed, so the callback never sees a freed token
+                        using var registration = cancellationToken.Register(
+                            static state => NativeMethods.typst_net_cancel_token_cancel((nint)state!),
+                            cancelToken
+                        );
 
-                    return ConvertCompileResult(result);
+                        result = NativeMethods.typst_net_compiler_compile_with_cancel(
+                            _handle!.DangerousGetHandle(),
+                            sourcePtr,
+                            (nuint)byteCount,
+                            cancelToken
+                        );
+                    }
                 }
             }
+
+            if (result.ErrorKind == ErrorKind.Cancelled)
+            {
+                NativeMethods.typst_net_result_free(result);
+                throw new OperationCanceledException(cancellationToken);
+            }
+
+            return ConvertCompileResult(result);
         }
         finally
         {
+            if (cancelToken != 0)
+                NativeMethods.typst_net_cancel_token_free(cancelToken);
+
             if (rentedArray != null)
                 System.Buffers.ArrayPool<byte>.Shared.Return(rentedArray);
         }
     }
 
+    /// <summary>
+    /// Number of canceled or timed-out compilations still running natively.
+    /// </summary>
+    /// <remarks>
+    /// Each one keeps a CPU core busy until it finishes. Cancelable compilations share a bounded pool of native
+    /// threads, so while abandoned ones fill it, new ones wait. Hosts compiling untrusted templates can recycle
+    /// the process when this stays high.
+    /// </remarks>
+    public static long AbandonedCompilations => (long)NativeMethods.typst_net_abandoned_compiles();
+
     /// <summary>
     /// Reset the compilation cache.
     /// For long-running processes, call periodically to free old cached data.
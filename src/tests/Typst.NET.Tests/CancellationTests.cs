@@ -0,0 +1,48 @@
+﻿using Xunit;
+
+namespace Typst.NET.Tests;
+
+public sealed class CancellationTests
+{
+    [Fact]
+    public void Compile_PrecanceledToken_Throws()
+    {
+        // Arrange
+        using var compiler = new TypstCompiler(Path.GetTempPath());
+        using var cts = new CancellationTokenSource();
+        cts.Cancel();
+
+        // Act & Assert
+        Assert.Throws<OperationCanceledException>(() => compiler.Compile("= Never", cts.Token));
+    }
+
+    [Fact]
+    public void Compile_CanceledDuringComputation_ReturnsPromptly()
+    {
+        // Arrange
+        using var compiler = new TypstCompiler(Path.GetTempPath());
+        using var cts = new CancellationTokenSource(TimeSpan.FromMilliseconds(100));
+        var stopwatch = System.Diagnostics.Stopwatch.StartNew();
+
+        // Act & Assert: never touches a file, would run for many seconds
+        Assert.Throws<OperationCanceledException>(
+            () => compiler.Compile("#for i in range(100000000) {}", cts.Token)
+        );
+        Assert.True(stopwatch.Elapsed < TimeSpan.FromSeconds(5));
+    }
+
+    [Fact]
+    public void Compile_UncanceledToken_Succeeds()
+    {
+        // Arrange
+        using var compiler = new TypstCompiler(Path.GetTempPath());
+        using var cts = new CancellationTokenSource();
+
+        // Act
+        using var result = compiler.Compile("= Hello", cts.Token);
+
+        // Assert
+        Assert.True(result.Success);
+        Assert.NotNull(result.Document);
+    }
+}
@@ -22,4 +22,32 @@ fn main() {
 
     println!("cargo:rustc-env=TYPST_VERSION={}", typst_version);
     println!("cargo:rerun-if-changed=Cargo.toml");
-}
\ No newline at end of file
+
+    generate_header(Path::new(&manifest_dir));
+}
+
+/// Write the C header for the FFI surface to `$OUT_DIR/typst_net.h`
+///
+/// The source tree is never written to, so read-only and vendored builds work.
+/// `test_c_header_up_to_date` fails when the committed include/typst_net.h
+/// differs, so any change to an export or a repr(C) type shows up in review
+/// as a header diff.
+fn generate_header(manifest_dir: &Path) {
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=src/types.rs");
+    println!("cargo:rerun-if-changed=src/watch.rs");
+
+    let config = cbindgen::Config::from_file(manifest_dir.join("cbindgen.toml"))
+        .expect("invalid cbindgen.toml");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    match cbindgen::generate_with_config(manifest_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(Path::new(&out_dir).join("typst_net.h"));
+        }
+        // A parse failure shouldn't block building the library itself; the
+        // header test fails instead
+        Err(e) => println!("cargo:warning=failed to generate typst_net.h: {}", e),
+    }
+}
@@ -0,0 +1,917 @@
+#ifndef TYPST_NET_H
+#define TYPST_NET_H
+
+/* Generated by cbindgen from the FFI surface (src/lib.rs, src/types.rs). Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Version of the binary interface (exports and struct layouts)
+ *
+ * Bumped when an export or struct changes incompatibly. Appending fields to
+ * `CompilerOptions` does not bump it; `struct_size` covers those.
+ */
+#define TYPST_NET_ABI_VERSION 1
+
+/**
+ * Severity level for diagnostics
+ */
+enum TypstNetDiagnosticSeverity
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : uint8_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  TYPST_NET_DIAGNOSTIC_SEVERITY_ERROR = 0,
+  TYPST_NET_DIAGNOSTIC_SEVERITY_WARNING = 1,
+  TYPST_NET_DIAGNOSTIC_SEVERITY_HINT = 2,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum TypstNetDiagnosticSeverity TypstNetDiagnosticSeverity;
+#else
+typedef uint8_t TypstNetDiagnosticSeverity;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+/**
+ * Why an operation failed
+ */
+enum TypstNetErrorKind
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : uint8_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  /**
+   * The operation succeeded
+   */
+  TYPST_NET_ERROR_KIND_NONE = 0,
+  /**
+   * Compilation errors (see diagnostics) or a rendering failure
+   */
+  TYPST_NET_ERROR_KIND_FAILED = 1,
+  /**
+   * Compilation took longer than `max_compile_time_ms`
+   */
+  TYPST_NET_ERROR_KIND_TIME_LIMIT_EXCEEDED = 2,
+  /**
+   * The document has more pages than `max_pages`
+   */
+  TYPST_NET_ERROR_KIND_PAGE_LIMIT_EXCEEDED = 3,
+  /**
+   * Rendered output is larger than `max_output_bytes`
+   */
+  TYPST_NET_ERROR_KIND_OUTPUT_LIMIT_EXCEEDED = 4,
+  /**
+   * The operation's cancellation token was triggered
+   */
+  TYPST_NET_ERROR_KIND_CANCELLED = 5,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum TypstNetErrorKind TypstNetErrorKind;
+#else
+typedef uint8_t TypstNetErrorKind;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+typedef struct TypstNetCompilerOptions {
+  /**
+   * Size of this struct as the caller compiled it (`sizeof(CompilerOptions)`).
+   * Fields past it were added after the caller was built and keep their defaults
+   */
+  size_t struct_size;
+  /**
+   * Include system fonts (default: true)
+   */
+  bool include_system_fonts;
+  /**
+   * JSON string of inputs {"key" : "content"}
+   */
+  const uint8_t *inputs_json;
+  size_t inputs_json_len;
+  /**
+   * Custom font paths (array of UTF-8 strings)
+   */
+  const uint8_t *custom_font_paths;
+  size_t custom_font_paths_len;
+  /**
+   * Package path for offline packages (unpacked directories or `.tar.gz` archives)
+   */
+  const uint8_t *package_path;
+  size_t package_path_len;
+  /**
+   * Sandbox: allowed file extensions (JSON array of strings, e.g. ["typ", "png"]).
+   * Null or empty allows every extension
+   */
+  const uint8_t *allowed_extensions;
+  size_t allowed_extensions_len;
+  /**
+   * Sandbox: maximum size of a single file read from disk (0 = unlimited)
+   */
+  uint64_t max_file_size;
+  /**
+   * Sandbox: maximum bytes read from disk per compilation (0 = unlimited)
+   */
+  uint64_t max_total_read_bytes;
+  /**
+   * Sandbox: deny all filesystem access, only the main source is available
+   */
+  bool forbid_filesystem;
+  /**
+   * Limit: maximum wall-clock time per compilation in milliseconds (0 = unlimited).
+   * The compile call returns at the limit, but the CPU is not reclaimed: typst
+   * keeps running on a pool thread until it finishes (see `typst_net_abandoned_compiles`)
+   */
+  uint64_t max_compile_time_ms;
+  /**
+   * Limit: maximum number of pages per document (0 = unlimited), checked after layout
+   */
+  uint64_t max_pages;
+  /**
+   * Limit: maximum size of a single render call's output in bytes (0 = unlimited)
+   */
+  uint64_t max_output_bytes;
+  /**
+   * Use `fixed_date_unix_seconds` as the current date instead of the clock
+   * (otherwise `SOURCE_DATE_EPOCH` is honored when set)
+   */
+  bool use_fixed_date;
+  /**
+   * Fixed date as Unix timestamp in seconds, used by `datetime.today()` and PDF metadata
+   */
+  int64_t fixed_date_unix_seconds;
+  /**
+   * Threads used to render pages in parallel (0 = one per CPU core)
+   */
+  uint32_t render_threads;
+  /**
+   * Include the fonts bundled with the library (needs the `embed-fonts` feature)
+   */
+  bool include_embedded_fonts;
+  /**
+   * Cache directory for downloaded packages. Null or empty disables downloading
+   */
+  const uint8_t *package_cache_path;
+  size_t package_cache_path_len;
+  /**
+   * Registry base URL packages are downloaded from (null = https://packages.typst.org)
+   */
+  const uint8_t *package_registry_url;
+  size_t package_registry_url_len;
+  /**
+   * Only use packages already in the cache, never download
+   */
+  bool offline_packages;
+  /**
+   * More package roots searched after `package_path`, in order (JSON array of strings)
+   */
+  const uint8_t *package_paths;
+  size_t package_paths_len;
+  /**
+   * Search the user package directory (where the typst CLI keeps `@local`) last
+   */
+  bool include_user_packages;
+} TypstNetCompilerOptions;
+
+/**
+ * Buffer containing UTF-8 or binary data
+ */
+typedef struct TypstNetBuffer {
+  uint8_t *data;
+  size_t len;
+} TypstNetBuffer;
+
+/**
+ * Source code location information
+ */
+typedef struct TypstNetSourceLocation {
+  /**
+   * 1-indexed line number (0 if unavailable)
+   */
+  uint32_t line;
+  /**
+   * 1-indexed column number (0 if unavailable)
+   */
+  uint32_t column;
+  /**
+   * Length of the span in characters (0 if unavailable)
+   */
+  uint32_t length;
+} TypstNetSourceLocation;
+
+/**
+ * A single diagnostic message
+ */
+typedef struct TypstNetDiagnostic {
+  TypstNetDiagnosticSeverity severity;
+  /**
+   * UTF-8 message bytes
+   */
+  uint8_t *message;
+  size_t message_len;
+  /**
+   * Location (all zeros if unavailable)
+   */
+  struct TypstNetSourceLocation location;
+} TypstNetDiagnostic;
+
+/**
+ * Result of a compilation operation
+ */
+typedef struct TypstNetCompileResult {
+  /**
+   * True if compilation succeeded
+   */
+  bool success;
+  /**
+   * Array of diagnostics (always present, even if empty)
+   */
+  struct TypstNetDiagnostic *diagnostics;
+  size_t diagnostics_len;
+  /**
+   * Opaque document handle (null if compilation failed)
+   */
+  void *document;
+  /**
+   * Reason for failure (`None` on success)
+   */
+  TypstNetErrorKind error_kind;
+} TypstNetCompileResult;
+
+/**
+ * Array of compile results (batch compilation)
+ */
+typedef struct TypstNetCompileResultArray {
+  struct TypstNetCompileResult *results;
+  size_t len;
+} TypstNetCompileResultArray;
+
+/**
+ * Array of buffers (for multipage SVG)
+ */
+typedef struct TypstNetBufferArray {
+  struct TypstNetBuffer *buffers;
+  size_t len;
+} TypstNetBufferArray;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+const uint8_t *typst_net_version(void);
+
+size_t typst_net_version_len(void);
+
+/**
+ * Get the ABI version of the loaded library
+ *
+ * Hosts should compare it with the `TYPST_NET_ABI_VERSION` they were built
+ * against and refuse to use the library when they differ.
+ */
+uint32_t typst_net_abi_version(void);
+
+/**
+ * Create a new compiler instance
+ *
+ * # Arguments
+ * * `root_path` - UTF-8 encoded path to workspace root
+ * * `root_path_len` - Length of root_path in bytes
+ * * `options` - Compiler configuration options (can be null for defaults)
+ *
+ * # Options fields (all optional, pass null struct for defaults):
+ * * `struct_size` - Required: `sizeof(CompilerOptions)` as the caller knows it. Fields
+ *   added in later library versions keep their defaults; too small a size fails
+ * * `include_system_fonts` - Whether to load system fonts (default: true)
+ * * `include_embedded_fonts` - Whether to load the bundled fonts (default: true;
+ *   always off when built without the `embed-fonts` feature)
+ * * `inputs_json` - JSON object string of inputs: {"key": "value"}
+ * * `custom_font_paths` - Array of font directory paths (TODO: not yet implemented)
+ * * `package_path` - Path for offline packages, unpacked as `namespace/name/version/`
+ *   or archived as `namespace/name-version.tar.gz`
+ * * `package_paths` - JSON array of more package roots, searched after `package_path`
+ *   in order (earlier roots override packages of later ones)
+ * * `include_user_packages` - Search the user package directory last, where the typst
+ *   CLI keeps `@local` packages (e.g. `~/.local/share/typst/packages`)
+ * * `package_cache_path`, `package_registry_url`, `offline_packages` - Download packages
+ *   missing from the package roots into the cache (no cache path = no downloads;
+ *   `@local` packages are never downloaded)
+ * * `allowed_extensions`, `max_file_size`, `max_total_read_bytes`, `forbid_filesystem` -
+ *   Sandbox policy for files read from disk (zero/empty = unrestricted)
+ * * `max_compile_time_ms`, `max_pages`, `max_output_bytes` - Resource limits (0 = unlimited).
+ *   A compilation past its time limit is abandoned: the call returns, but typst keeps
+ *   its CPU core busy until it finishes (see `typst_net_abandoned_compiles`). The page
+ *   count is checked once layout finished
+ * * `use_fixed_date`, `fixed_date_unix_seconds` - Fixed current date for reproducible output
+ *   (falls back to `SOURCE_DATE_EPOCH`, then the system clock)
+ * * `render_threads` - Threads used to render pages in parallel (0 = one per CPU core)
+ *
+ * # Returns
+ * Opaque pointer to compiler instance, or null on failure
+ *
+ * # Safety
+ * * `root_path` must point to valid UTF-8 bytes
+ * * `options` pointers must remain valid during this call
+ * * Caller must free returned pointer with `typst_net_compiler_free`
+ * * All memory in `options` is borrowed - caller retains ownership
+ *
+ * # Memory
+ * This function does NOT take ownership of any pointers in `options`.
+ * Caller may free option strings immediately after this call returns.
+ */
+void *typst_net_compiler_create(const uint8_t *root_path,
+                                size_t root_path_len,
+                                const struct TypstNetCompilerOptions *options);
+
+/**
+ * Create a new compiler instance that uses a shared font store
+ *
+ * Skips the font search, so creating short-lived compilers is cheap.
+ * The font fields of `options` (`include_system_fonts`, `include_embedded_fonts`,
+ * `custom_font_paths`) are ignored.
+ *
+ * # Arguments
+ * * `root_path` - UTF-8 encoded path to workspace root
+ * * `root_path_len` - Length of root_path in bytes
+ * * `options` - Compiler configuration options (can be null for defaults)
+ * * `font_store` - Store from `typst_net_font_store_create`
+ *
+ * # Returns
+ * Opaque pointer to compiler instance, or null on failure
+ *
+ * # Safety
+ * * Same requirements as `typst_net_compiler_create`
+ * * `font_store` must be a valid pointer from `typst_net_font_store_create`.
+ *   It may be freed while the compiler is still in use.
+ */
+void *typst_net_compiler_create_with_fonts(const uint8_t *root_path,
+                                           size_t root_path_len,
+                                           const struct TypstNetCompilerOptions *options,
+                                           const void *font_store);
+
+/**
+ * Free a compiler instance
+ *
+ * Compilations running on other threads must have finished.
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `compiler` must not be used after this call
+ * - Must only be called once per compiler
+ */
+void typst_net_compiler_free(void *compiler);
+
+/**
+ * Replace the inputs (`sys.inputs`) of a compiler
+ *
+ * Only the standard library is rebuilt; loaded fonts are reused, so one
+ * compiler can serve many documents that differ only in their inputs.
+ *
+ * # Arguments
+ * * `compiler` - Valid compiler pointer
+ * * `inputs_json` - JSON object string of inputs: {"key": "value"} (null clears all inputs)
+ * * `inputs_json_len` - Length of inputs_json in bytes
+ *
+ * # Returns
+ * true on success; false on invalid JSON, leaving the previous inputs in place.
+ * Compilations already running keep the inputs they started with.
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `inputs_json` must be null or valid UTF-8
+ */
+bool typst_net_compiler_set_inputs(void *compiler,
+                                   const uint8_t *inputs_json,
+                                   size_t inputs_json_len);
+
+/**
+ * Search fonts once for use by many compilers
+ *
+ * Only the font options (`include_system_fonts`, `include_embedded_fonts`,
+ * `custom_font_paths`) of `options` are used.
+ *
+ * # Returns
+ * Opaque font store pointer, or null on failure (including a too small `struct_size`).
+ * Caller must free with `typst_net_font_store_free`
+ *
+ * # Safety
+ * * `options` must be null or valid during this call (borrowed, like `typst_net_compiler_create`)
+ */
+void *typst_net_font_store_create(const struct TypstNetCompilerOptions *options);
+
+/**
+ * Free a font store
+ *
+ * Compilers created from the store keep working; they hold their own reference.
+ *
+ * # Safety
+ * - `font_store` must be a valid pointer from `typst_net_font_store_create`
+ * - Must only be called once per store
+ */
+void typst_net_font_store_free(void *font_store);
+
+/**
+ * Add fonts from memory to a compiler
+ *
+ * Parses a font file or collection (TTF, OTF, TTC, OTC) and makes every face
+ * in it available to subsequent compilations of this compiler only.
+ *
+ * # Arguments
+ * * `compiler` - Valid compiler pointer
+ * * `data` - Font file bytes (copied; caller retains ownership)
+ * * `data_len` - Length of data in bytes
+ *
+ * # Returns
+ * Number of font faces added, 0 if the data is not a valid font
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `data` must point to `data_len` readable bytes
+ */
+size_t typst_net_compiler_add_font(void *compiler, const uint8_t *data, size_t data_len);
+
+/**
+ * List every font available to a compiler as JSON
+ *
+ * Fonts are listed in lookup order. Example entry:
+ * `{"family": "Inter", "style": "normal", "weight": 400, "stretch": 1.0,
+ *   "source": "custom", "path": "/fonts/Inter.ttf", "collection_index": 0,
+ *   "coverage": {"codepoints": 2548, "ranges": [[32, 126], ...]}}`
+ *
+ * `source` is "embedded", "system", "custom" or "memory"; `path` is null
+ * unless the font was loaded from a file.
+ *
+ * # Returns
+ * Buffer containing a UTF-8 JSON array - caller must free with `typst_net_buffer_free`.
+ * Empty buffer if `compiler` is null.
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ */
+struct TypstNetBuffer typst_net_compiler_list_fonts(const void *compiler);
+
+/**
+ * Compile typst source code
+ *
+ * Thread-safe: one compiler can run many compilations concurrently.
+ * Each call compiles its own source; fonts and library are shared.
+ *
+ * # Arguments
+ * * `compiler` - Valid compiler pointer
+ * * `source` - UTF-8 encoded source code
+ * * `source_len` - Length of source in bytes
+ *
+ * # Returns
+ * CompileResult - caller must free with `typst_net_result_free`
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `source` must be valid UTF-8
+ */
+struct TypstNetCompileResult typst_net_compiler_compile(void *compiler,
+                                                        const uint8_t *source,
+                                                        size_t source_len);
+
+/**
+ * Compile typst source code, aborting once a cancellation token is triggered
+ *
+ * The call returns within milliseconds of the token being triggered, at any
+ * stage of evaluation or layout. typst itself can't be interrupted, so this
+ * releases the caller, not the CPU: the abandoned compilation keeps running
+ * on a pool thread until it finishes, failing its file reads (see
+ * `typst_net_abandoned_compiles`).
+ * A cancelled compilation returns `success = false` with `error_kind = Cancelled`.
+ *
+ * # Arguments
+ * * `compiler` - Valid compiler pointer
+ * * `source` - UTF-8 encoded source code
+ * * `source_len` - Length of source in bytes
+ * * `cancel_token` - Token from `typst_net_cancel_token_create` (can be null)
+ *
+ * # Returns
+ * CompileResult - caller must free with `typst_net_result_free`
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `source` must be valid UTF-8
+ * - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+ */
+struct TypstNetCompileResult typst_net_compiler_compile_with_cancel(void *compiler,
+                                                                    const uint8_t *source,
+                                                                    size_t source_len,
+                                                                    const void *cancel_token);
+
+/**
+ * Compile one template for many input sets (mail merge)
+ *
+ * Each entry of `inputs_json_array` replaces `sys.inputs` for one compilation.
+ * Entries are compiled in parallel and share fonts and caches. The compiler's
+ * own inputs are not changed.
+ *
+ * # Arguments
+ * * `compiler` - Valid compiler pointer
+ * * `source` - UTF-8 encoded template source code
+ * * `source_len` - Length of source in bytes
+ * * `inputs_json_array` - JSON array of input objects: [{"name": "A"}, {"name": "B"}]
+ * * `inputs_json_array_len` - Length of inputs_json_array in bytes
+ *
+ * # Returns
+ * One CompileResult per entry, in input order. Entries that are not objects
+ * (or null) yield a failed result. An empty JSON array yields an empty array
+ * with a non-null `results` pointer; null array on invalid arguments or JSON.
+ * Caller must free with `typst_net_result_array_free`
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `source` and `inputs_json_array` must be valid UTF-8
+ */
+struct TypstNetCompileResultArray typst_net_compiler_compile_batch(void *compiler,
+                                                                   const uint8_t *source,
+                                                                   size_t source_len,
+                                                                   const uint8_t *inputs_json_array,
+                                                                   size_t inputs_json_array_len);
+
+/**
+ * Compile one template for many input sets with cancellation support
+ *
+ * Same as `typst_net_compiler_compile_batch`, but once `cancel_token` is
+ * triggered every entry still running is abandoned with
+ * `ErrorKind::Cancelled` and entries not started yet are skipped. Like
+ * `typst_net_compiler_compile_with_cancel`, this releases the caller, not
+ * the CPU (see `typst_net_abandoned_compiles`).
+ *
+ * # Arguments
+ * * `compiler` - Valid compiler pointer
+ * * `source` - UTF-8 encoded template source code
+ * * `source_len` - Length of source in bytes
+ * * `inputs_json_array` - JSON array of input objects
+ * * `inputs_json_array_len` - Length of inputs_json_array in bytes
+ * * `cancel_token` - Token from `typst_net_cancel_token_create` (can be null)
+ *
+ * # Returns
+ * One CompileResult per entry, in input order (see `typst_net_compiler_compile_batch`).
+ * Caller must free with `typst_net_result_array_free`
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ * - `source` and `inputs_json_array` must be valid UTF-8
+ * - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+ */
+struct TypstNetCompileResultArray typst_net_compiler_compile_batch_with_cancel(void *compiler,
+                                                                               const uint8_t *source,
+                                                                               size_t source_len,
+                                                                               const uint8_t *inputs_json_array,
+                                                                               size_t inputs_json_array_len,
+                                                                               const void *cancel_token);
+
+/**
+ * List the files the compiler's last finished compilation read from disk
+ *
+ * Covers workspace and package files accessed through `typst_net_compiler_compile`
+ * and `typst_net_compiler_compile_with_cancel`; batch compiles don't update it.
+ * Example: `["/work/chapter.typ", "/work/logo.png", "/data/packages/preview/cetz-0.3.0.tar.gz"]`
+ *
+ * Paths are canonical, in order of first request. Files that were missing or
+ * could not be read are included, as creating or fixing them changes the
+ * output. Package files loaded from an archive are reported as the archive.
+ * The main source is not included.
+ *
+ * # Returns
+ * Buffer containing a UTF-8 JSON array - caller must free with `typst_net_buffer_free`.
+ * Empty buffer if `compiler` is null.
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create`
+ */
+struct TypstNetBuffer typst_net_compiler_dependencies(const void *compiler);
+
+/**
+ * Check typst source code for syntax errors only
+ *
+ * Parses the source without creating a compiler, loading fonts or touching
+ * the filesystem. Semantic errors (unknown variables, missing files, ...)
+ * are not reported.
+ *
+ * # Arguments
+ * * `source` - UTF-8 encoded source code
+ * * `source_len` - Length of source in bytes
+ *
+ * # Returns
+ * CompileResult with syntax errors as diagnostics and a null document.
+ * Caller must free with `typst_net_result_free`
+ *
+ * # Safety
+ * - `source` must be valid UTF-8
+ */
+struct TypstNetCompileResult typst_net_check_syntax(const uint8_t *source, size_t source_len);
+
+/**
+ * Free a compilation result
+ *
+ * # Safety
+ * - `result` must be from a `typst_net_compiler_compile` call
+ * - must only be called once per result
+ */
+void typst_net_result_free(struct TypstNetCompileResult result);
+
+/**
+ * Free an array of compilation results, including every document in it
+ *
+ * # Safety
+ * - `array` must be from `typst_net_compiler_compile_batch` or
+ *   `typst_net_compiler_compile_batch_with_cancel`
+ * - Documents in the array must not be used after this call
+ * - Must only be called once per array
+ */
+void typst_net_result_array_free(struct TypstNetCompileResultArray array);
+
+/**
+ * Create a cancellation token
+ *
+ * Pass the token to `*_with_cancel` functions, then call
+ * `typst_net_cancel_token_cancel` from any thread to stop them.
+ *
+ * # Returns
+ * Opaque token pointer - caller must free with `typst_net_cancel_token_free`
+ */
+void *typst_net_cancel_token_create(void);
+
+/**
+ * Trigger a cancellation token
+ *
+ * Safe to call from any thread, including while the token is in use.
+ * Cancellation is permanent; create a new token for the next operation.
+ *
+ * # Safety
+ * - `token` must be a valid pointer from `typst_net_cancel_token_create`
+ */
+void typst_net_cancel_token_cancel(const void *token);
+
+/**
+ * Free a cancellation token
+ *
+ * A compilation still using the token keeps its own reference,
+ * so freeing here never invalidates an in-flight operation.
+ *
+ * # Safety
+ * - `token` must be a valid pointer from `typst_net_cancel_token_create`
+ * - Must only be called once per token
+ */
+void typst_net_cancel_token_free(void *token);
+
+/**
+ * Number of compilations abandoned at their time limit or on cancellation
+ * that are still queued or running
+ *
+ * typst can't be interrupted, so an abandoned compilation keeps its thread
+ * and CPU core busy until it finishes. Compilations with a time limit or
+ * cancellation token run on a pool of one thread per core (at least 4);
+ * while abandoned ones fill it, new ones queue and fail at their own limit.
+ * Hosts compiling untrusted templates can recycle the process when this
+ * count stays high.
+ */
+size_t typst_net_abandoned_compiles(void);
+
+/**
+ * Compile a source and recompile it whenever a file it depends on changes
+ *
+ * Compiles right away, then watches the workspace root and the package
+ * directories the last compilation used with filesystem notifications.
+ * Changes to files the last compilation requested trigger a recompilation;
+ * after a failed compilation any change does. Bursts of changes are coalesced.
+ *
+ * Every result is passed to `callback(result, user_data)` on a background
+ * thread. The callback owns the result and must free it with
+ * `typst_net_result_free`.
+ *
+ * # Arguments
+ * * `compiler` - Compiler to compile with (may be used concurrently by other calls)
+ * * `source` / `source_len` - UTF-8 main source, copied
+ * * `callback` - Receives each compile result
+ * * `user_data` - Passed through to `callback`
+ *
+ * # Returns
+ * Opaque watch handle - stop with `typst_net_watch_stop`.
+ * Null if an argument is invalid or watching failed.
+ *
+ * # Safety
+ * - `compiler` must be a valid pointer from `typst_net_compiler_create` and
+ *   must not be freed before the watch is stopped
+ * - `source` must point to `source_len` bytes
+ * - `callback` and `user_data` must be safe to use from another thread
+ */
+void *typst_net_watch_start(const void *compiler,
+                            const uint8_t *source,
+                            size_t source_len,
+                            void (*callback)(struct TypstNetCompileResult result, void *user_data),
+                            void *user_data);
+
+/**
+ * Stop a watch
+ *
+ * Cancels a compilation in progress and blocks until its (cancelled) result
+ * has been delivered; no callback runs after this returns.
+ *
+ * # Safety
+ * - `watch` must be a valid pointer from `typst_net_watch_start`
+ * - Must only be called once per watch, and never from inside the callback
+ */
+void typst_net_watch_stop(void *watch);
+
+/**
+ * Get the number of pages in a document
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+size_t typst_net_document_page_count(const void *document);
+
+/**
+ * Render a single page to SVG
+ *
+ * # Arguments
+ * * `document` - Valid document pointer
+ * * `page_index` - 0-indexed page number
+ *
+ * # Returns
+ * Buffer containing SVG data - caller must free with `typst_net_buffer_free`
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ * - `page_index` must be < page_count
+ */
+struct TypstNetBuffer typst_net_document_render_svg_page(const void *document, size_t page_index);
+
+/**
+ * Render all pages to SVG
+ *
+ * # Returns
+ * BufferArray containing SVG data for each page - caller must free with `typst_net_buffer_array_free`
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+struct TypstNetBufferArray typst_net_document_render_svg_all(const void *document);
+
+/**
+ * Render all pages to SVG, checking a cancellation token before each page
+ *
+ * # Returns
+ * BufferArray containing SVG data for each page, or an empty array if cancelled
+ * (`typst_net_document_last_error` then reports `Cancelled`)
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ * - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+ */
+struct TypstNetBufferArray typst_net_document_render_svg_all_with_cancel(const void *document,
+                                                                         const void *cancel_token);
+
+/**
+ * Render a single page to PNG
+ *
+ * # Arguments
+ * * `document` - Valid document pointer
+ * * `page_index` - Zero-based page index
+ * * `pixels_per_pt` - Resolution (1.0 = 72 DPI, 2.0 = 144 DPI)
+ *
+ * # Returns
+ * Buffer containing PNG data - caller must free with `typst_net_buffer_free`
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ * - `page_index` must be < page_count
+ */
+struct TypstNetBuffer typst_net_document_render_png_page(const void *document,
+                                                         size_t page_index,
+                                                         float pixels_per_pt);
+
+/**
+ * Render all pages to PNG, in parallel on `render_threads` threads
+ *
+ * # Returns
+ * BufferArray containing PNG data for each page - caller must free with `typst_net_buffer_array_free`
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+struct TypstNetBufferArray typst_net_document_render_png_all(const void *document,
+                                                             float pixels_per_pt);
+
+/**
+ * Render all pages to PNG, checking a cancellation token before each page
+ *
+ * # Returns
+ * BufferArray containing PNG data for each page, or an empty array if cancelled
+ * (`typst_net_document_last_error` then reports `Cancelled`)
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ * - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+ */
+struct TypstNetBufferArray typst_net_document_render_png_all_with_cancel(const void *document,
+                                                                         float pixels_per_pt,
+                                                                         const void *cancel_token);
+
+/**
+ * Render document to PDF
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+struct TypstNetBuffer typst_net_document_render_pdf(const void *document);
+
+/**
+ * Render document to PDF, checking a cancellation token
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ * - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+ */
+struct TypstNetBuffer typst_net_document_render_pdf_with_cancel(const void *document,
+                                                                const void *cancel_token);
+
+/**
+ * Report which fonts a document uses and which characters have no glyph
+ *
+ * Characters no loaded font covers are drawn as empty boxes ("tofu");
+ * they are listed in `missing_glyphs`. Example:
+ * `{"fonts": [{"family": "Libertinus Serif", "style": "normal", "weight": 400, "pages": [0, 1]}],
+ *   "missing_glyphs": [{"text": "漢", "codepoints": [28450], "font_family": "Libertinus Serif", "pages": [1]}]}`
+ * Page indices are zero-based.
+ *
+ * # Returns
+ * Buffer containing UTF-8 JSON - caller must free with `typst_net_buffer_free`
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+struct TypstNetBuffer typst_net_document_font_report(const void *document);
+
+/**
+ * List every package a document was compiled from (a bill of materials)
+ *
+ * Example:
+ * `[{"spec": "@preview/cetz:0.3.0", "namespace": "preview", "name": "cetz", "version": "0.3.0",
+ *    "path": "/data/packages/preview/cetz/0.3.0", "kind": "directory"}]`
+ * `path` is the resolved package directory or, for `kind` "archive", the `.tar.gz` file.
+ * Packages are listed in order of first use.
+ *
+ * # Returns
+ * Buffer containing UTF-8 JSON - caller must free with `typst_net_buffer_free`
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+struct TypstNetBuffer typst_net_document_packages(const void *document);
+
+/**
+ * Get the reason the last render call on a document failed
+ *
+ * Render functions return an empty buffer on failure; this tells whether
+ * that was caused by the `max_output_bytes` limit or another error.
+ *
+ * # Returns
+ * `ErrorKind::None` if the last render succeeded (or nothing was rendered yet)
+ *
+ * # Safety
+ * - `document` must be a valid pointer from a successful CompileResult
+ */
+TypstNetErrorKind typst_net_document_last_error(const void *document);
+
+/**
+ * Free a buffer
+ *
+ * # Safety
+ * - `buffer` must be from a typst_net_* function
+ * - Must only be called once per buffer
+ */
+void typst_net_buffer_free(struct TypstNetBuffer buffer);
+
+/**
+ * Free a buffer array
+ *
+ * # Safety
+ * - `array` must be from typst_net_document_render_svg_all
+ * - Must only be called once per array
+ */
+void typst_net_buffer_array_free(struct TypstNetBufferArray array);
+
+/**
+ * Reset the compilation cache
+ *
+ * For long-running processes, call this periodically to evict old cached data
+ *
+ * # Arguments
+ * * `max_age_seconds` - Evict cache entries older than this (0 = evict all)
+ */
+void typst_net_reset_cache(size_t max_age_seconds);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TYPST_NET_H */
@@ -0,0 +1,313 @@
+// Command-line driver for the core, to triage rendering issues and bisect
+// typst upgrades without the .NET wrapper.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{env, fs, slice};
+
+use typst_net_core::native::{
+    CompileResult, CompilerInstance, CompilerOptions, DiagnosticSeverity, DocumentInstance,
+};
+use typst_net_core::typst_net_result_free;
+
+const USAGE: &str = "\
+Usage: typst-net <INPUT> [OUTPUT] [OPTIONS]
+
+Compiles INPUT and writes OUTPUT (default: INPUT with a .pdf extension).
+The format follows the extension of OUTPUT: .pdf, .svg or .png. For SVG and
+PNG, `{p}` in OUTPUT is replaced by the page number; without it, documents
+with several pages are written as NAME-1.svg, NAME-2.svg, ...
+
+Options:
+  --root <DIR>            Workspace root (default: directory of INPUT)
+  --inputs <JSON>         sys.inputs as a JSON object
+  --input <KEY=VALUE>     Add one sys.inputs entry (repeatable)
+  --font-path <DIR>       Custom font directory (repeatable)
+  --system-fonts          Also load system fonts
+  --package-path <DIR>    Directory with offline packages
+  --ppi <N>               PNG resolution in pixels per inch (default: 144)
+  -h, --help              Print this help";
+
+/// Parsed command line
+#[derive(Debug, Default)]
+struct Args {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    root: Option<PathBuf>,
+    inputs: serde_json::Map<String, serde_json::Value>,
+    font_paths: Vec<String>,
+    system_fonts: bool,
+    package_path: Option<String>,
+    ppi: f32,
+}
+
+/// Output format, chosen by the output file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Pdf,
+    Svg,
+    Png,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parse the arguments, `None` if help was requested
+fn parse_args(mut raw: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut args = Args {
+        ppi: 144.0,
+        ..Args::default()
+    };
+    let mut positional = Vec::new();
+
+    while let Some(arg) = raw.next() {
+        let mut value = |name: &str| raw.next().ok_or(format!("{} needs a value", name));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--root" => args.root = Some(PathBuf::from(value("--root")?)),
+            "--inputs" => {
+                let json: serde_json::Value = serde_json::from_str(&value("--inputs")?)
+                    .map_err(|e| format!("invalid --inputs JSON: {}", e))?;
+                let serde_json::Value::Object(map) = json else {
+                    return Err("--inputs must be a JSON object".into());
+                };
+                args.inputs.extend(map);
+            }
+            "--input" => {
+                let entry = value("--input")?;
+                let (key, val) = entry
+                    .split_once('=')
+                    .ok_or(format!("--input expects KEY=VALUE, got `{}`", entry))?;
+                args.inputs.insert(key.into(), val.into());
+            }
+            "--font-path" => args.font_paths.push(value("--font-path")?),
+            "--system-fonts" => args.system_fonts = true,
+            "--package-path" => args.package_path = Some(value("--package-path")?),
+            "--ppi" => {
+                let ppi = value("--ppi")?;
+                args.ppi = ppi
+                    .parse()
+                    .ok()
+                    .filter(|ppi: &f32| *ppi > 0.0)
+                    .ok_or(format!("invalid --ppi `{}`", ppi))?;
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option `{}`", flag));
+            }
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    args.input = positional.next().ok_or("missing INPUT")?;
+    args.output = positional.next();
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument `{}`", extra.display()));
+    }
+
+    Ok(Some(args))
+}
+
+/// Compile and write the output, returning whether compilation succeeded
+fn run(args: &Args) -> Result<bool, String> {
+    let source = fs::read_to_string(&args.input)
+        .map_err(|e| format!("failed to read {}: {}", args.input.display(), e))?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input.with_extension("pdf"));
+    let format = match output.extension().and_then(|ext| ext.to_str()) {
+        Some("pdf") => Format::Pdf,
+        Some("svg") => Format::Svg,
+        Some("png") => Format::Png,
+        _ => return Err(format!("unknown output format for {}", output.display())),
+    };
+
+    let root = match &args.root {
+        Some(root) => root.clone(),
+        None => match args.input.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        },
+    };
+
+    // The options borrow these until the compiler is created
+    let inputs_json = serde_json::Value::Object(args.inputs.clone()).to_string();
+    let font_paths_json = serde_json::to_string(&args.font_paths).unwrap_or_default();
+    let package_path = args.package_path.clone().unwrap_or_default();
+
+    let options = CompilerOptions {
+        include_system_fonts: args.system_fonts,
+        inputs_json: inputs_json.as_ptr(),
+        inputs_json_len: inputs_json.len(),
+        custom_font_paths: font_paths_json.as_ptr(),
+        custom_font_paths_len: font_paths_json.len(),
+        package_path: package_path.as_ptr(),
+        package_path_len: package_path.len(),
+        ..CompilerOptions::default()
+    };
+
+    let compiler = CompilerInstance::new(root, &options)?;
+    let (result, files) = compiler.compile_file(&args.input, &source)?;
+
+    print_diagnostics(&result, &files);
+    let written = if result.success {
+        let document = unsafe { &*(result.document as *const DocumentInstance) };
+        write_output(document, format, &output, args.ppi)
+    } else {
+        Ok(())
+    };
+    let success = result.success;
+
+    unsafe { typst_net_result_free(result) };
+    written.map(|_| success)
+}
+
+/// Print errors and warnings to stderr, `files` holds the file of each diagnostic
+fn print_diagnostics(result: &CompileResult, files: &[Option<PathBuf>]) {
+    let diagnostics = if result.diagnostics.is_null() {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(result.diagnostics, result.diagnostics_len) }
+    };
+
+    let current_dir = env::current_dir().unwrap_or_default();
+    for (diagnostic, file) in diagnostics.iter().zip(files) {
+        let severity = match diagnostic.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Hint => "hint",
+        };
+        let message = unsafe { slice::from_raw_parts(diagnostic.message, diagnostic.message_len) };
+        eprintln!("{}: {}", severity, String::from_utf8_lossy(message));
+
+        let location = &diagnostic.location;
+        if let Some(file) = file
+            && location.line > 0
+        {
+            eprintln!(
+                "  --> {}:{}:{}",
+                file.strip_prefix(&current_dir).unwrap_or(file).display(),
+                location.line,
+                location.column
+            );
+        }
+    }
+}
+
+/// Render the document in `format` and write it to `output`
+fn write_output(
+    document: &DocumentInstance,
+    format: Format,
+    output: &Path,
+    ppi: f32,
+) -> Result<(), String> {
+    let pages = match format {
+        Format::Pdf => {
+            let pdf = document.render_pdf(None)?;
+            return write_file(output, &pdf);
+        }
+        Format::Svg => document.render_all_pages_svg(None)?,
+        Format::Png => document.render_all_pages_png(ppi / 72.0, None)?,
+    };
+
+    let template = output.to_string_lossy();
+    for (index, page) in pages.iter().enumerate() {
+        let number = (index + 1).to_string();
+        let path = if template.contains("{p}") {
+            PathBuf::from(template.replace("{p}", &number))
+        } else if pages.len() > 1 {
+            numbered_path(output, &number)
+        } else {
+            output.to_path_buf()
+        };
+        write_file(&path, page)?;
+    }
+
+    Ok(())
+}
+
+/// `dir/name.ext` to `dir/name-number.ext`
+fn numbered_path(output: &Path, number: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}-{}.{}", stem, number, extension))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    eprintln!("wrote {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&[
+            "doc.typ",
+            "out.png",
+            "--inputs",
+            r#"{"a": "1"}"#,
+            "--input",
+            "b=x=y",
+            "--font-path",
+            "fonts",
+            "--ppi",
+            "300",
+        ])
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(args.input, PathBuf::from("doc.typ"));
+        assert_eq!(args.output, Some(PathBuf::from("out.png")));
+        assert_eq!(
+            serde_json::Value::Object(args.inputs),
+            serde_json::json!({"a": "1", "b": "x=y"})
+        );
+        assert_eq!(args.font_paths, vec!["fonts"]);
+        assert_eq!(args.ppi, 300.0);
+
+        assert!(parse(&["--help"]).unwrap().is_none());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["doc.typ", "--ppi", "0"]).is_err());
+        assert!(parse(&["doc.typ", "--input", "novalue"]).is_err());
+        assert!(parse(&["doc.typ", "--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_numbered_path() {
+        assert_eq!(
+            numbered_path(Path::new("out/page.svg"), "2"),
+            PathBuf::from("out/page-2.svg")
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// Cooperative cancellation shared between the host and running compilations.
+
+use std::ffi::c_void;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Flag that a host thread sets to stop an in-flight compile or render.
+///
+/// Handed across FFI as a raw `Arc`, so a compilation holding a clone keeps
+/// the token alive even if the host frees its handle early.
+#[derive(Debug, Default)]
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+}
+
+impl CancellationToken {
+    /// Request cancellation of every operation observing this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Convert a token into an owned raw handle for FFI
+    pub fn into_raw(token: Arc<Self>) -> *mut c_void {
+        Arc::into_raw(token) as *mut c_void
+    }
+
+    /// Clone the token behind a raw handle without consuming the handle
+    ///
+    /// # Safety
+    /// - `handle` must be null or a live pointer from `into_raw`
+    pub unsafe fn clone_from_raw(handle: *const c_void) -> Option<Arc<Self>> {
+        if handle.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let ptr = handle as *const Self;
+            Arc::increment_strong_count(ptr);
+            Some(Arc::from_raw(ptr))
+        }
+    }
+
+    /// Release a raw handle created by `into_raw`
+    ///
+    /// # Safety
+    /// - `handle` must be a pointer from `into_raw`
+    /// - Must only be called once per handle
+    pub unsafe fn free_raw(handle: *mut c_void) {
+        if !handle.is_null() {
+            unsafe {
+                drop(Arc::from_raw(handle as *const Self));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_is_observed() {
+        let token = CancellationToken::default();
+        assert!(!token.is_cancelled());
+
+        token.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_raw_handle_roundtrip() {
+        let handle = CancellationToken::into_raw(Arc::new(CancellationToken::default()));
+
+        unsafe {
+            let clone = CancellationToken::clone_from_raw(handle).unwrap();
+            CancellationToken::free_raw(handle);
+
+            // The clone outlives the freed handle
+            clone.cancel();
+            assert!(clone.is_cancelled());
+            assert_eq!(Arc::strong_count(&clone), 1);
+
+            assert!(CancellationToken::clone_from_raw(std::ptr::null()).is_none());
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// Bounded pool of threads for compilations that may be abandoned.
+// typst can't be stopped mid-evaluation, so a timed-out or cancelled compile
+// keeps its thread until it finishes; the pool caps how many threads that is.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Condvar, LazyLock, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// Stack of worker threads; typst recurses deeply on nested content
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Workers started even on machines with fewer cores, so one abandoned
+/// compilation doesn't stall every other one
+const MIN_WORKERS: usize = 4;
+
+/// Job states, see `PoolJob`
+const PENDING: u8 = 0;
+const FINISHED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Jobs abandoned by their caller that are still queued or running
+static ABANDONED_JOBS: AtomicUsize = AtomicUsize::new(0);
+
+static POOL: LazyLock<Pool> = LazyLock::new(|| Pool {
+    max_workers: thread::available_parallelism().map_or(MIN_WORKERS, |n| n.get().max(MIN_WORKERS)),
+    queue: Mutex::default(),
+    available: Condvar::new(),
+});
+
+thread_local! {
+    static ON_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads are started on demand, one per CPU core (at least `MIN_WORKERS`)
+struct Pool {
+    max_workers: usize,
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    workers: usize,
+    idle: usize,
+}
+
+/// A job submitted to the pool
+pub struct PoolJob<T> {
+    receiver: Receiver<thread::Result<T>>,
+    state: Arc<AtomicU8>,
+}
+
+/// Run `job` on a pool thread
+///
+/// Jobs wait in a queue while every worker is busy, including workers still
+/// running abandoned jobs. Fails only if no worker thread could be started.
+pub fn submit<T: Send + 'static>(
+    job: impl FnOnce() -> T + Send + 'static,
+) -> Result<PoolJob<T>, String> {
+    let (sender, receiver) = mpsc::channel();
+    let state = Arc::new(AtomicU8::new(PENDING));
+
+    let job_state = Arc::clone(&state);
+    let job: Job = Box::new(move || {
+        // Nobody waits for it anymore, don't spend CPU on it
+        if job_state.load(Ordering::Acquire) == ABANDONED {
+            ABANDONED_JOBS.fetch_sub(1, Ordering::AcqRel);
+            return;
+        }
+
+        let result = panic::catch_unwind(AssertUnwindSafe(job));
+        match job_state.compare_exchange(PENDING, FINISHED, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                let _ = sender.send(result);
+            }
+            Err(_) => {
+                ABANDONED_JOBS.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+    });
+
+    POOL.push(job)?;
+    Ok(PoolJob { receiver, state })
+}
+
+/// Number of jobs given up on by their caller that are still queued or running
+///
+/// Each running one occupies a worker (and a CPU core) until typst finishes.
+pub fn abandoned_jobs() -> usize {
+    ABANDONED_JOBS.load(Ordering::Acquire)
+}
+
+/// Whether the current thread is a pool worker
+///
+/// Jobs must not submit to the pool and wait, as they could wait for a
+/// queue that only they would drain.
+pub fn on_worker() -> bool {
+    ON_WORKER.with(Cell::get)
+}
+
+impl<T> PoolJob<T> {
+    /// Wait for the job's result, checking `interrupted` every `poll`
+    ///
+    /// Returns `None` as soon as `interrupted` returns true, abandoning the
+    /// job: a queued job is skipped, a running one finishes unobserved. A
+    /// panic in the job is resumed on this thread.
+    pub fn wait(self, poll: Duration, interrupted: impl Fn() -> bool) -> Option<T> {
+        loop {
+            match self.receiver.recv_timeout(poll) {
+                Ok(Ok(value)) => return Some(value),
+                Ok(Err(payload)) => panic::resume_unwind(payload),
+                Err(RecvTimeoutError::Timeout) => {
+                    if !interrupted() {
+                        continue;
+                    }
+                    // Counted before the state changes, so the worker never
+                    // decrements first
+                    ABANDONED_JOBS.fetch_add(1, Ordering::AcqRel);
+                    if self
+                        .state
+                        .compare_exchange(PENDING, ABANDONED, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+                    {
+                        return None;
+                    }
+                    // Finished meanwhile, the result is on its way
+                    ABANDONED_JOBS.fetch_sub(1, Ordering::AcqRel);
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    unreachable!("pool job dropped without a result")
+                }
+            }
+        }
+    }
+}
+
+impl Pool {
+    fn push(&'static self, job: Job) -> Result<(), String> {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.jobs.push_back(job);
+
+        if queue.jobs.len() > queue.idle && queue.workers < self.max_workers {
+            let spawned = thread::Builder::new()
+                .name("typst-compile".into())
+                .stack_size(WORKER_STACK_SIZE)
+                .spawn(move || self.work());
+            match spawned {
+                Ok(_) => queue.workers += 1,
+                // Existing workers will get to the job eventually
+                Err(_) if queue.workers > 0 => {}
+                Err(e) => {
+                    queue.jobs.pop_back();
+                    return Err(format!("Failed to start compile thread: {}", e));
+                }
+            }
+        }
+
+        self.available.notify_one();
+        Ok(())
+    }
+
+    fn work(&self) {
+        ON_WORKER.with(|on_worker| on_worker.set(true));
+
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+                loop {
+                    if let Some(job) = queue.jobs.pop_front() {
+                        break job;
+                    }
+                    queue.idle += 1;
+                    queue = self
+                        .available
+                        .wait(queue)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    queue.idle -= 1;
+                }
+            };
+            job();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Instant;
+
+    #[test]
+    fn test_pool_returns_results() {
+        let job = submit(|| 6 * 7).unwrap();
+        assert_eq!(job.wait(Duration::from_millis(1), || false), Some(42));
+    }
+
+    #[test]
+    fn test_pool_abandons_and_skips_jobs() {
+        let release = Arc::new(AtomicBool::new(false));
+        let blocked = Arc::clone(&release);
+        let running = submit(move || {
+            while !blocked.load(Ordering::Acquire) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        })
+        .unwrap();
+
+        let started = Instant::now();
+        assert_eq!(running.wait(Duration::from_millis(1), || true), None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(abandoned_jobs() >= 1);
+
+        release.store(true, Ordering::Release);
+        // Other tests may abandon jobs concurrently, only ours has to drain
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&ran);
+        let after = submit(move || flag.store(true, Ordering::Release)).unwrap();
+        assert_eq!(
+            after.wait(Duration::from_millis(1), || Instant::now() > deadline),
+            Some(())
+        );
+        assert!(ran.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_pool_resumes_panics() {
+        let job = submit(|| panic!("boom")).unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            job.wait(Duration::from_millis(1), || false)
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -1,14 +1,27 @@
 // High-level compiler logic using internal types only.
 // No direct typst imports. Everything goes through typst_backend.
 
+use crate::cancel::CancellationToken;
 use crate::memory::{create_diagnostic, diagnostics_to_array};
-use crate::types::{CompileResult, CompilerOptions, Diagnostic, DiagnosticSeverity};
-use crate::typst_backend::{BackendCompileResult, BackendDocument, BackendWorld};
-use std::path::PathBuf;
+use crate::types::{CompileResult, CompilerOptions, Diagnostic, DiagnosticSeverity, ErrorKind};
+use crate::typst_backend::{
+    AbortReason, BackendCompileResult, BackendDocument, BackendFontEntry, BackendWorld,
+    DEFAULT_PACKAGE_REGISTRY, FontSource, FontStore, PackageRegistry, RenderError, ResourceLimits,
+    SandboxPolicy, check_syntax, user_package_path,
+};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 
+/// Compiler handle that can be shared between threads
+///
+/// The world holds fonts, library and settings only. Every compilation runs on
+/// its own snapshot of it, so compiles never block each other.
 pub struct CompilerInstance {
-    world: BackendWorld,
+    world: RwLock<BackendWorld>,
+    /// Files read by the compilation that finished last
+    dependencies: Mutex<Vec<PathBuf>>,
 }
 
 impl CompilerInstance {
@@ -17,43 +30,209 @@ impl CompilerInstance {
     /// Parses all options and create world.
     /// Does NOT retain any pointers from options.
     pub fn new(root: PathBuf, options: &CompilerOptions) -> Result<Self, String> {
+        let font_store = FontStoreInstance::new(options)?;
+        Self::with_font_store(root, options, &font_store)
+    }
+
+    /// Create a compiler that uses an existing font store
+    ///
+    /// The font options (`include_system_fonts`, `include_embedded_fonts`,
+    /// `custom_font_paths`) are ignored.
+    pub fn with_font_store(
+        root: PathBuf,
+        options: &CompilerOptions,
+        font_store: &FontStoreInstance,
+    ) -> Result<Self, String> {
         // Parse inputs from JSON (or empty dict)
         let inputs = Self::parse_inputs(options)?;
 
-        let package_path = if !options.package_path.is_null() && options.package_path_len > 0 {
-            unsafe {
-                let path_bytes =
-                    std::slice::from_raw_parts(options.package_path, options.package_path_len);
-                let path_str =
-                    std::str::from_utf8(path_bytes).map_err(|_| "Invalid UTF-8 in package path")?;
-                Some(PathBuf::from(path_str))
-            }
-        } else {
-            None
+        let package_paths = Self::parse_package_paths(options)?;
+
+        let sandbox = SandboxPolicy {
+            allowed_extensions: Self::parse_allowed_extensions(options)?,
+            max_file_size: options.max_file_size,
+            max_total_bytes: options.max_total_read_bytes,
+            forbid_filesystem: options.forbid_filesystem,
+        };
+
+        let mut world =
+            BackendWorld::with_fonts(root, inputs.as_deref(), package_paths, &font_store.fonts)?;
+        world.set_sandbox(sandbox);
+        world.set_limits(ResourceLimits {
+            max_compile_time_ms: options.max_compile_time_ms,
+            max_pages: options.max_pages,
+            max_output_bytes: options.max_output_bytes,
+        });
+        world.set_fixed_date(Self::resolve_fixed_date(options)?)?;
+        world.set_render_threads(options.render_threads as usize)?;
+        world.set_package_registry(Self::parse_package_registry(options)?)?;
+
+        Ok(Self {
+            world: RwLock::new(world),
+            dependencies: Mutex::default(),
+        })
+    }
+
+    /// Replace the inputs (`sys.inputs`) for subsequent compilations
+    ///
+    /// Reuses loaded fonts, so this is much cheaper than creating a new compiler.
+    /// `None` clears all inputs. Compilations already running keep their inputs.
+    pub fn set_inputs(&self, inputs_json: Option<&str>) -> Result<(), String> {
+        self.world
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_inputs(inputs_json)
+    }
+
+    /// Describe every loaded font face as a JSON array
+    ///
+    /// Each entry has `family`, `style`, `weight`, `stretch`, `source`
+    /// ("embedded", "system", "custom" or "memory"), `path` (null unless loaded
+    /// from a file), `collection_index` and `coverage` (`codepoints` count and
+    /// inclusive `ranges`).
+    pub fn list_fonts_json(&self) -> String {
+        let fonts: Vec<serde_json::Value> = self
+            .world
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .list_fonts()
+            .into_iter()
+            .map(font_entry_to_json)
+            .collect();
+
+        serde_json::Value::Array(fonts).to_string()
+    }
+
+    /// Add fonts from a font file or collection in memory for subsequent compilations
+    ///
+    /// Returns the number of font faces added (0 if `data` is not a font).
+    /// Only this compiler sees the fonts, even when created from a shared font store.
+    pub fn add_font(&self, data: Vec<u8>) -> usize {
+        self.world
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .add_font(data)
+    }
+
+    /// Compile `source`
+    pub fn compile(&self, source: &str) -> CompileResult {
+        self.compile_tracked(source, None).0
+    }
+
+    /// Compile `source` and also return the files the compilation requested
+    ///
+    /// Aborts once `cancel_token` (if any) is triggered.
+    pub fn compile_tracked(
+        &self,
+        source: &str,
+        cancel_token: Option<Arc<CancellationToken>>,
+    ) -> (CompileResult, Vec<PathBuf>) {
+        let mut world = self.snapshot(source);
+        let backend_result = match cancel_token {
+            Some(cancel_token) => world.compile_with_cancel(cancel_token),
+            None => world.compile(),
         };
-        
-        let custom_font_paths = Self::parse_custom_font_paths(options)?;
+        let dependencies = world.dependencies();
+        self.set_dependencies(dependencies.clone());
+        (Self::convert_result(backend_result), dependencies)
+    }
 
-        let world = BackendWorld::new(
-            root,
-            inputs.as_deref(),
-            package_path,
-            custom_font_paths,
-            options.include_system_fonts,
-        )?;
+    /// Compile `source` as the file at `path` under the root, like the typst CLI
+    ///
+    /// Relative paths in `source` resolve against the file's directory. Also
+    /// returns the file each diagnostic points into, in diagnostic order
+    /// (`None` for diagnostics without a location).
+    pub fn compile_file(
+        &self,
+        path: &Path,
+        source: &str,
+    ) -> Result<(CompileResult, Vec<Option<PathBuf>>), String> {
+        let mut world = self.snapshot(source);
+        world.set_main_file(path)?;
+        let backend_result = world.compile();
+        self.set_dependencies(world.dependencies());
+
+        let files = backend_result
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.file.clone())
+            .collect();
+        Ok((Self::convert_result(backend_result), files))
+    }
 
-        Ok(Self { world })
+    /// Compile `source`, aborting once `cancel_token` is triggered
+    pub fn compile_with_cancel(
+        &self,
+        source: &str,
+        cancel_token: Arc<CancellationToken>,
+    ) -> CompileResult {
+        self.compile_tracked(source, Some(cancel_token)).0
     }
 
-    /// Update the source code to compile
-    pub fn update_source(&mut self, source: &str) {
-        self.world.update_source(source);
+    /// Compile `source` once per input set in a JSON array, in parallel
+    ///
+    /// Entries share fonts and caches; results keep the input order. Every
+    /// entry aborts once `cancel_token` (if any) is triggered.
+    pub fn compile_batch(
+        &self,
+        source: &str,
+        inputs_json_array: &str,
+        cancel_token: Option<Arc<CancellationToken>>,
+    ) -> Result<Vec<CompileResult>, String> {
+        let backend_results = self
+            .snapshot(source)
+            .compile_batch(inputs_json_array, cancel_token)?;
+        Ok(backend_results
+            .into_iter()
+            .map(Self::convert_result)
+            .collect())
     }
 
-    /// Compile the current source
-    pub fn compile(&mut self) -> CompileResult {
-        let backend_result: BackendCompileResult = self.world.compile();
+    /// Files requested from disk by the compilation that finished last, as a JSON array
+    ///
+    /// Covers `compile` and `compile_with_cancel` (batch compiles don't update
+    /// it). Paths are canonical, in order of first request, and include files
+    /// that were missing. Package files loaded from an archive are reported as
+    /// the `.tar.gz` file.
+    pub fn dependencies_json(&self) -> String {
+        let dependencies: Vec<serde_json::Value> = self
+            .dependencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|path| serde_json::Value::from(path.display().to_string()))
+            .collect();
 
+        serde_json::Value::Array(dependencies).to_string()
+    }
+
+    /// Store the files a finished compilation read
+    fn set_dependencies(&self, dependencies: Vec<PathBuf>) {
+        *self
+            .dependencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = dependencies;
+    }
+
+    /// Workspace root files are resolved against (canonical)
+    pub fn root(&self) -> PathBuf {
+        self.world
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .root()
+            .to_path_buf()
+    }
+
+    /// Take a world to compile `source` on, without holding the lock while compiling
+    fn snapshot(&self, source: &str) -> BackendWorld {
+        self.world
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .snapshot(source)
+    }
+
+    /// Convert a backend result into an FFI result owned by the caller
+    fn convert_result(backend_result: BackendCompileResult) -> CompileResult {
         // Convert backend diagnostics to FFI diagnostics
         let diagnostics = backend_result
             .diagnostics
@@ -63,6 +242,12 @@ impl CompilerInstance {
 
         let (diagnostics_ptr, diagnostics_len) = diagnostics_to_array(diagnostics);
 
+        let error_kind = match backend_result.aborted {
+            Some(reason) => abort_error_kind(reason),
+            None if backend_result.success => ErrorKind::None,
+            None => ErrorKind::Failed,
+        };
+
         // Convert the document if present
         let document_ptr = if let Some(backend_doc) = backend_result.document {
             Box::into_raw(Box::new(DocumentInstance::new(backend_doc))) as *mut std::ffi::c_void
@@ -75,6 +260,7 @@ impl CompilerInstance {
             diagnostics: diagnostics_ptr,
             diagnostics_len,
             document: document_ptr,
+            error_kind,
         }
     }
 
@@ -98,13 +284,86 @@ impl CompilerInstance {
             Ok(Some(json_str.to_string()))
         }
     }
-    
+
+    /// Collect the package roots in lookup order
+    ///
+    /// `package_path` first, then the `package_paths` JSON array, then the user
+    /// package directory if `include_user_packages` is set (and it exists).
+    fn parse_package_paths(options: &CompilerOptions) -> Result<Vec<PathBuf>, String> {
+        let mut paths = Vec::new();
+
+        unsafe {
+            if !options.package_path.is_null() && options.package_path_len > 0 {
+                let path_bytes =
+                    std::slice::from_raw_parts(options.package_path, options.package_path_len);
+                let path_str =
+                    std::str::from_utf8(path_bytes).map_err(|_| "Invalid UTF-8 in package path")?;
+                paths.push(PathBuf::from(path_str));
+            }
+
+            if !options.package_paths.is_null() && options.package_paths_len > 0 {
+                let json_bytes =
+                    std::slice::from_raw_parts(options.package_paths, options.package_paths_len);
+                let json_str = std::str::from_utf8(json_bytes)
+                    .map_err(|_| "Invalid UTF-8 in package paths")?;
+
+                let extra: Vec<String> = serde_json::from_str(json_str)
+                    .map_err(|e| format!("Invalid package paths JSON: {}", e))?;
+                paths.extend(extra.into_iter().map(PathBuf::from));
+            }
+        }
+
+        if options.include_user_packages {
+            paths.extend(user_package_path());
+        }
+
+        Ok(paths)
+    }
+
+    /// Parse the package download settings (`None` if no cache path is set)
+    fn parse_package_registry(
+        options: &CompilerOptions,
+    ) -> Result<Option<PackageRegistry>, String> {
+        if options.package_cache_path.is_null() || options.package_cache_path_len == 0 {
+            return Ok(None);
+        }
+
+        unsafe {
+            let path_bytes = std::slice::from_raw_parts(
+                options.package_cache_path,
+                options.package_cache_path_len,
+            );
+            let cache_path = std::str::from_utf8(path_bytes)
+                .map_err(|_| "Invalid UTF-8 in package cache path")?;
+
+            let url = if options.package_registry_url.is_null()
+                || options.package_registry_url_len == 0
+            {
+                DEFAULT_PACKAGE_REGISTRY.to_string()
+            } else {
+                let url_bytes = std::slice::from_raw_parts(
+                    options.package_registry_url,
+                    options.package_registry_url_len,
+                );
+                std::str::from_utf8(url_bytes)
+                    .map_err(|_| "Invalid UTF-8 in package registry URL")?
+                    .to_string()
+            };
+
+            Ok(Some(PackageRegistry {
+                url,
+                cache_path: PathBuf::from(cache_path),
+                offline: options.offline_packages,
+            }))
+        }
+    }
+
     /// Parse custom font paths from JSON array
     fn parse_custom_font_paths(options: &CompilerOptions) -> Result<Vec<PathBuf>, String> {
         if options.custom_font_paths.is_null() || options.custom_font_paths_len == 0 {
             return Ok(Vec::new());
         }
-        
+
         unsafe {
             let json_bytes = std::slice::from_raw_parts(
                 options.custom_font_paths,
@@ -121,17 +380,117 @@ impl CompilerInstance {
             Ok(paths.into_iter().map(PathBuf::from).collect())
         }
     }
+
+    /// Resolve the fixed date: explicit option first, then `SOURCE_DATE_EPOCH`
+    fn resolve_fixed_date(options: &CompilerOptions) -> Result<Option<i64>, String> {
+        if options.use_fixed_date {
+            return Ok(Some(options.fixed_date_unix_seconds));
+        }
+
+        match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(value) => parse_source_date_epoch(&value).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Parse sandbox allowed extensions from JSON array
+    ///
+    /// Extensions are normalized to lowercase without a leading dot.
+    fn parse_allowed_extensions(options: &CompilerOptions) -> Result<Vec<String>, String> {
+        if options.allowed_extensions.is_null() || options.allowed_extensions_len == 0 {
+            return Ok(Vec::new());
+        }
+
+        unsafe {
+            let json_bytes = std::slice::from_raw_parts(
+                options.allowed_extensions,
+                options.allowed_extensions_len,
+            );
+
+            let json_str = std::str::from_utf8(json_bytes)
+                .map_err(|_| "Invalid UTF-8 in allowed extensions")?;
+
+            let extensions: Vec<String> = serde_json::from_str(json_str)
+                .map_err(|e| format!("Invalid allowed extensions JSON: {}", e))?;
+
+            Ok(extensions
+                .into_iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect())
+        }
+    }
+}
+
+/// Check source code for syntax errors without compiling it
+///
+/// Only parses the source, so no world or fonts are created.
+/// The result never carries a document.
+pub fn check_source_syntax(source: &str) -> CompileResult {
+    let diagnostics: Vec<Diagnostic> = check_syntax(source)
+        .into_iter()
+        .map(convert_backend_diagnostic)
+        .collect();
+
+    let success = diagnostics.is_empty();
+    let (diagnostics_ptr, diagnostics_len) = diagnostics_to_array(diagnostics);
+
+    CompileResult {
+        success,
+        diagnostics: diagnostics_ptr,
+        diagnostics_len,
+        document: ptr::null_mut(),
+        error_kind: if success {
+            ErrorKind::None
+        } else {
+            ErrorKind::Failed
+        },
+    }
+}
+
+/// Parse a `SOURCE_DATE_EPOCH` value (Unix timestamp in seconds)
+fn parse_source_date_epoch(value: &str) -> Result<i64, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid SOURCE_DATE_EPOCH: {}", value))
+}
+
+/// Font index shared by many compilers
+///
+/// Fonts are searched once on creation; compilers keep their own reference,
+/// so the store may be freed while they are still in use.
+pub struct FontStoreInstance {
+    fonts: FontStore,
+}
+
+impl FontStoreInstance {
+    /// Search fonts according to the font options of `options`
+    pub fn new(options: &CompilerOptions) -> Result<Self, String> {
+        let custom_font_paths = CompilerInstance::parse_custom_font_paths(options)?;
+        Ok(Self {
+            fonts: FontStore::search(
+                custom_font_paths,
+                options.include_system_fonts,
+                options.include_embedded_fonts,
+            ),
+        })
+    }
 }
 
 /// Internal representation of a document instance
 pub struct DocumentInstance {
     backend_doc: BackendDocument,
+    /// `ErrorKind` of the last render call
+    last_error: AtomicU8,
 }
 
 impl DocumentInstance {
     /// Create a new document instance from backend document
     pub fn new(backend_doc: BackendDocument) -> Self {
-        Self { backend_doc }
+        Self {
+            backend_doc,
+            last_error: AtomicU8::new(ErrorKind::None as u8),
+        }
     }
 
     /// Get the number of pages in the document
@@ -141,17 +500,161 @@ impl DocumentInstance {
 
     /// Render a single page to SVG
     pub fn render_page_svg(&self, page_index: usize) -> Result<Vec<u8>, String> {
-        self.backend_doc.render_page_svg(page_index)
+        self.track(self.backend_doc.render_page_svg(page_index))
     }
 
     /// Render all pages to SVG
-    pub fn render_all_pages_svg(&self) -> Result<Vec<Vec<u8>>, String> {
-        self.backend_doc.render_all_pages_svg()
+    pub fn render_all_pages_svg(
+        &self,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<Vec<u8>>, String> {
+        self.track(self.backend_doc.render_all_pages_svg(cancel))
+    }
+
+    /// Render a single page to PNG
+    pub fn render_page_png(
+        &self,
+        page_index: usize,
+        pixels_per_pt: f32,
+    ) -> Result<Vec<u8>, String> {
+        self.track(self.backend_doc.render_page_png(page_index, pixels_per_pt))
+    }
+
+    /// Render all pages to PNG
+    pub fn render_all_pages_png(
+        &self,
+        pixels_per_pt: f32,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<Vec<u8>>, String> {
+        self.track(self.backend_doc.render_all_pages_png(pixels_per_pt, cancel))
     }
 
     /// Render document to PDF
-    pub fn render_pdf(&self) -> Result<Vec<u8>, String> {
-        self.backend_doc.render_pdf()
+    pub fn render_pdf(&self, cancel: Option<&CancellationToken>) -> Result<Vec<u8>, String> {
+        self.track(self.backend_doc.render_pdf(cancel))
+    }
+
+    /// Fonts used per page and characters without a glyph, as JSON
+    ///
+    /// `{"fonts": [{"family", "style", "weight", "pages"}],
+    ///   "missing_glyphs": [{"text", "codepoints", "font_family", "pages"}]}`
+    /// with zero-based page indices, in order of first use.
+    pub fn font_report_json(&self) -> String {
+        let report = self.backend_doc.font_report();
+
+        let fonts: Vec<serde_json::Value> = report
+            .fonts
+            .into_iter()
+            .map(|font| {
+                serde_json::json!({
+                    "family": font.family,
+                    "style": font.style,
+                    "weight": font.weight,
+                    "pages": font.pages,
+                })
+            })
+            .collect();
+
+        let missing_glyphs: Vec<serde_json::Value> = report
+            .missing_glyphs
+            .into_iter()
+            .map(|glyph| {
+                let codepoints: Vec<u32> = glyph.text.chars().map(u32::from).collect();
+                serde_json::json!({
+                    "text": glyph.text,
+                    "codepoints": codepoints,
+                    "font_family": glyph.font_family,
+                    "pages": glyph.pages,
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "fonts": fonts,
+            "missing_glyphs": missing_glyphs,
+        })
+        .to_string()
+    }
+
+    /// Packages the document was compiled from, as a JSON array
+    ///
+    /// `[{"spec", "namespace", "name", "version", "path", "kind"}]` in order of
+    /// first use, where `kind` is "directory" or "archive".
+    pub fn packages_json(&self) -> String {
+        let packages: Vec<serde_json::Value> = self
+            .backend_doc
+            .packages()
+            .iter()
+            .map(|package| {
+                serde_json::json!({
+                    "spec": format!("@{}/{}:{}", package.namespace, package.name, package.version),
+                    "namespace": package.namespace,
+                    "name": package.name,
+                    "version": package.version,
+                    "path": package.path.display().to_string(),
+                    "kind": if package.archive { "archive" } else { "directory" },
+                })
+            })
+            .collect();
+
+        serde_json::Value::Array(packages).to_string()
+    }
+
+    /// Failure reason of the last render call
+    pub fn last_error(&self) -> ErrorKind {
+        ErrorKind::from_u8(self.last_error.load(Ordering::Relaxed))
+    }
+
+    /// Record the outcome of a render call for `last_error`
+    fn track<T>(&self, result: Result<T, RenderError>) -> Result<T, String> {
+        let kind = match &result {
+            Ok(_) => ErrorKind::None,
+            Err(RenderError::OutputLimitExceeded { .. }) => ErrorKind::OutputLimitExceeded,
+            Err(RenderError::Cancelled) => ErrorKind::Cancelled,
+            Err(RenderError::Failed(_)) => ErrorKind::Failed,
+        };
+        self.last_error.store(kind as u8, Ordering::Relaxed);
+
+        result.map_err(|e| e.to_string())
+    }
+}
+
+/// Convert a backend font description to its JSON form
+fn font_entry_to_json(entry: BackendFontEntry) -> serde_json::Value {
+    let (source, path) = match &entry.source {
+        FontSource::Embedded => ("embedded", None),
+        FontSource::System(path) => ("system", Some(path.display().to_string())),
+        FontSource::Custom(path) => ("custom", Some(path.display().to_string())),
+        FontSource::Memory => ("memory", None),
+    };
+
+    let codepoints: u32 = entry
+        .coverage
+        .iter()
+        .map(|(start, end)| end - start + 1)
+        .sum();
+
+    serde_json::json!({
+        "family": entry.family,
+        "style": entry.style,
+        "weight": entry.weight,
+        "stretch": entry.stretch,
+        "source": source,
+        "path": path,
+        "collection_index": entry.collection_index,
+        "coverage": {
+            "codepoints": codepoints,
+            "ranges": entry.coverage,
+        },
+    })
+}
+
+/// Map a backend abort reason to its FFI error kind
+fn abort_error_kind(reason: AbortReason) -> ErrorKind {
+    match reason {
+        AbortReason::TimeLimit => ErrorKind::TimeLimitExceeded,
+        AbortReason::PageLimit => ErrorKind::PageLimitExceeded,
+        AbortReason::Cancelled => ErrorKind::Cancelled,
     }
 }
 
@@ -175,18 +678,11 @@ mod tests {
     use crate::compiler::CompilerInstance;
     use crate::types::CompilerOptions;
     use std::env;
-    use std::ptr;
 
-    //noinspection DuplicatedCode
     fn default_options() -> CompilerOptions {
         CompilerOptions {
             include_system_fonts: true,
-            inputs_json: ptr::null(),
-            inputs_json_len: 0,
-            custom_font_paths: ptr::null(),
-            custom_font_paths_len: 0,
-            package_path: ptr::null(),
-            package_path_len: 0,
+            ..CompilerOptions::default()
         }
     }
 
@@ -212,10 +708,9 @@ mod tests {
     fn test_compile_simple_document() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Hello World\n\nTest content.");
-        let result = compiler.compile();
+        let result = compiler.compile("= Hello World\n\nTest content.");
 
         assert!(result.success);
         assert!(!result.document.is_null());
@@ -233,10 +728,9 @@ mod tests {
     fn test_compile_with_errors() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("#let x = (unclosed");
-        let result = compiler.compile();
+        let result = compiler.compile("#let x = (unclosed");
 
         assert!(!result.success);
         assert!(result.document.is_null());
@@ -252,16 +746,14 @@ mod tests {
     fn test_multiple_compilations() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
         // First compilation
-        compiler.update_source("= First");
-        let result1 = compiler.compile();
+        let result1 = compiler.compile("= First");
         assert!(result1.success);
 
         // Second compilation with different source
-        compiler.update_source("= Second\n\nNew content.");
-        let result2 = compiler.compile();
+        let result2 = compiler.compile("= Second\n\nNew content.");
         assert!(result2.success);
 
         // Clean up
@@ -277,14 +769,47 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compile_file_resolves_relative_to_it() {
+        let root = env::temp_dir().join("typst_compile_file_test");
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("main.typ"), "#let part = [Root]").unwrap();
+        std::fs::write(root.join("sub/part.typ"), "#let part = [Sub]").unwrap();
+        std::fs::write(root.join("sub/bad.typ"), "#undefined-variable").unwrap();
+        std::fs::write(root.join("sub/doc.typ"), "").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let compiler = CompilerInstance::new(root.clone(), &default_options()).unwrap();
+        let doc = root.join("sub/doc.typ");
+        let (imported, _) = compiler
+            .compile_file(&doc, "#import \"part.typ\": part\n#part")
+            .unwrap();
+        let (failed, files) = compiler.compile_file(&doc, "#include \"bad.typ\"").unwrap();
+        let outside = compiler.compile_file(&env::temp_dir(), "");
+
+        std::fs::remove_dir_all(&root).ok();
+
+        assert!(imported.success, "sub/part.typ should be imported");
+        assert!(!failed.success);
+        assert_eq!(files.len(), failed.diagnostics_len);
+        assert_eq!(files[0], Some(root.join("sub/bad.typ")));
+        assert!(outside.is_err());
+
+        unsafe {
+            let _ = Box::from_raw(imported.document as *mut DocumentInstance);
+            crate::memory::free_diagnostics(imported.diagnostics, imported.diagnostics_len);
+            crate::memory::free_diagnostics(failed.diagnostics, failed.diagnostics_len);
+        }
+    }
+
     #[test]
     fn test_document_page_count() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Page 1\n#pagebreak()\n= Page 2");
-        let result = compiler.compile();
+        let result = compiler.compile("= Page 1\n#pagebreak()\n= Page 2");
 
         assert!(result.success);
         assert!(!result.document.is_null());
@@ -298,4 +823,104 @@ mod tests {
             crate::memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
         }
     }
+
+    #[test]
+    fn test_check_syntax_valid_source() {
+        let result = check_source_syntax("= Title\n\n#let x = 5\n#x");
+
+        assert!(result.success);
+        assert!(result.document.is_null());
+        assert_eq!(result.diagnostics_len, 0);
+    }
+
+    #[test]
+    fn test_check_syntax_reports_errors() {
+        let result = check_source_syntax("= Title\n#let x = (unclosed");
+
+        assert!(!result.success);
+        assert!(result.document.is_null());
+        assert!(result.diagnostics_len > 0);
+
+        unsafe {
+            let diag = &*result.diagnostics;
+            assert!(matches!(diag.severity, DiagnosticSeverity::Error));
+            assert_eq!(diag.location.line, 2);
+
+            crate::memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
+        }
+    }
+
+    #[test]
+    fn test_check_syntax_ignores_semantic_errors() {
+        // Unknown variables and missing files are not syntax errors
+        let result = check_source_syntax("#undefined_variable\n#image(\"missing.png\")");
+
+        assert!(result.success);
+        assert_eq!(result.diagnostics_len, 0);
+    }
+
+    #[test]
+    fn test_compiler_sandbox_options() {
+        let extensions = r#"[".TYP", "json"]"#;
+        let options = CompilerOptions {
+            allowed_extensions: extensions.as_ptr(),
+            allowed_extensions_len: extensions.len(),
+            ..default_options()
+        };
+
+        let parsed = CompilerInstance::parse_allowed_extensions(&options).unwrap();
+        assert_eq!(parsed, vec!["typ".to_string(), "json".to_string()]);
+
+        let compiler = CompilerInstance::new(env::temp_dir(), &options).unwrap();
+        let result = compiler.compile(r#"#read("definitely-not-allowed.txt")"#);
+
+        assert!(!result.success);
+
+        unsafe {
+            crate::memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
+        }
+    }
+
+    #[test]
+    fn test_compiler_invalid_allowed_extensions() {
+        let extensions = "not json";
+        let options = CompilerOptions {
+            allowed_extensions: extensions.as_ptr(),
+            allowed_extensions_len: extensions.len(),
+            ..default_options()
+        };
+
+        assert!(CompilerInstance::new(env::temp_dir(), &options).is_err());
+    }
+
+    #[test]
+    fn test_fixed_date_option() {
+        let options = CompilerOptions {
+            use_fixed_date: true,
+            fixed_date_unix_seconds: 1_700_000_000,
+            ..default_options()
+        };
+
+        assert_eq!(
+            CompilerInstance::resolve_fixed_date(&options),
+            Ok(Some(1_700_000_000))
+        );
+
+        let compiler = CompilerInstance::new(env::temp_dir(), &options).unwrap();
+        let result = compiler.compile(r#"#assert.eq(datetime.today().year(), 2023)"#);
+
+        assert!(result.success);
+
+        unsafe {
+            let _ = Box::from_raw(result.document as *mut DocumentInstance);
+            crate::memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
+        }
+    }
+
+    #[test]
+    fn test_parse_source_date_epoch() {
+        assert_eq!(parse_source_date_epoch("1700000000"), Ok(1_700_000_000));
+        assert_eq!(parse_source_date_epoch(" 0\n"), Ok(0));
+        assert!(parse_source_date_epoch("yesterday").is_err());
+    }
 }
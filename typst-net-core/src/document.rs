@@ -1,6 +1,7 @@
+use crate::cancel::CancellationToken;
 use crate::compiler::DocumentInstance;
-use crate::memory::{vec_to_buffer, vecs_to_buffer_array};
-use crate::types::{Buffer, BufferArray};
+use crate::memory::{string_to_buffer, vec_to_buffer, vecs_to_buffer_array};
+use crate::types::{Buffer, BufferArray, ErrorKind};
 use std::ptr;
 
 /// Get the number of pages in a document
@@ -49,7 +50,10 @@ pub unsafe fn document_render_page_svg(
 /// # Safety
 /// - Document must be a valid pointer from a successful compilation
 /// - Caller must free the returned BufferArray with `free_buffer_array`
-pub unsafe fn document_render_all_pages_svg(document: *const DocumentInstance) -> BufferArray {
+pub unsafe fn document_render_all_pages_svg(
+    document: *const DocumentInstance,
+    cancel: Option<&CancellationToken>,
+) -> BufferArray {
     if document.is_null() {
         return BufferArray {
             buffers: ptr::null_mut(),
@@ -59,7 +63,7 @@ pub unsafe fn document_render_all_pages_svg(document: *const DocumentInstance) -
 
     let doc = unsafe { &*document };
 
-    match doc.render_all_pages_svg() {
+    match doc.render_all_pages_svg(cancel) {
         Ok(svg_pages) => vecs_to_buffer_array(svg_pages),
         Err(_) => BufferArray {
             buffers: ptr::null_mut(),
@@ -68,12 +72,68 @@ pub unsafe fn document_render_all_pages_svg(document: *const DocumentInstance) -
     }
 }
 
+/// Render a single page to PNG
+///
+/// # Safety
+/// - Document must be a valid pointer from a successful compilation
+/// - page_index must be < page_count
+/// - Caller must free the returned buffer with `free_buffer`
+pub unsafe fn document_render_page_png(
+    document: *const DocumentInstance,
+    page_index: usize,
+    pixels_per_pt: f32,
+) -> Buffer {
+    if document.is_null() {
+        return Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+    }
+
+    match unsafe { &*document }.render_page_png(page_index, pixels_per_pt) {
+        Ok(png_bytes) => vec_to_buffer(png_bytes),
+        Err(_) => Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        },
+    }
+}
+
+/// Render all pages to PNG
+///
+/// # Safety
+/// - Document must be a valid pointer from a successful compilation
+/// - Caller must free the returned BufferArray with `free_buffer_array`
+pub unsafe fn document_render_all_pages_png(
+    document: *const DocumentInstance,
+    pixels_per_pt: f32,
+    cancel: Option<&CancellationToken>,
+) -> BufferArray {
+    if document.is_null() {
+        return BufferArray {
+            buffers: ptr::null_mut(),
+            len: 0,
+        };
+    }
+
+    match unsafe { &*document }.render_all_pages_png(pixels_per_pt, cancel) {
+        Ok(png_pages) => vecs_to_buffer_array(png_pages),
+        Err(_) => BufferArray {
+            buffers: ptr::null_mut(),
+            len: 0,
+        },
+    }
+}
+
 /// Render entire document to PDF
 ///
 /// # Safety
 /// - Document must be a valid pointer from a successful compilation
 /// - Caller must free the returned buffer with `free_buffer`
-pub unsafe fn document_render_pdf(document: *const DocumentInstance) -> Buffer {
+pub unsafe fn document_render_pdf(
+    document: *const DocumentInstance,
+    cancel: Option<&CancellationToken>,
+) -> Buffer {
     if document.is_null() {
         return Buffer {
             data: ptr::null_mut(),
@@ -81,7 +141,7 @@ pub unsafe fn document_render_pdf(document: *const DocumentInstance) -> Buffer {
         };
     }
 
-    match unsafe { &*document }.render_pdf() {
+    match unsafe { &*document }.render_pdf(cancel) {
         Ok(pdf_bytes) => vec_to_buffer(pdf_bytes),
         Err(_) => Buffer {
             data: ptr::null_mut(),
@@ -90,6 +150,50 @@ pub unsafe fn document_render_pdf(document: *const DocumentInstance) -> Buffer {
     }
 }
 
+/// Report fonts used and missing glyphs as JSON
+///
+/// # Safety
+/// - Document must be a valid pointer from a successful compilation
+/// - Caller must free the returned buffer with `free_buffer`
+pub unsafe fn document_font_report(document: *const DocumentInstance) -> Buffer {
+    if document.is_null() {
+        return Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+    }
+
+    string_to_buffer(unsafe { &*document }.font_report_json())
+}
+
+/// List the packages a document was compiled from as JSON
+///
+/// # Safety
+/// - Document must be a valid pointer from a successful compilation
+/// - Caller must free the returned buffer with `free_buffer`
+pub unsafe fn document_packages(document: *const DocumentInstance) -> Buffer {
+    if document.is_null() {
+        return Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+    }
+
+    string_to_buffer(unsafe { &*document }.packages_json())
+}
+
+/// Get the failure reason of the last render call
+///
+/// # Safety
+/// - Document must be a valid pointer from a successful compilation
+pub unsafe fn document_last_error(document: *const DocumentInstance) -> ErrorKind {
+    if document.is_null() {
+        return ErrorKind::Failed;
+    }
+
+    unsafe { &*document }.last_error()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,16 +202,10 @@ mod tests {
     use std::env;
     use std::ptr;
 
-    //noinspection DuplicatedCode
     fn default_options() -> CompilerOptions {
         CompilerOptions {
             include_system_fonts: true,
-            inputs_json: ptr::null(),
-            inputs_json_len: 0,
-            custom_font_paths: ptr::null(),
-            custom_font_paths_len: 0,
-            package_path: ptr::null(),
-            package_path_len: 0,
+            ..CompilerOptions::default()
         }
     }
 
@@ -115,10 +213,9 @@ mod tests {
     fn test_document_page_count() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Page 1\n#pagebreak()\n= Page 2");
-        let result = compiler.compile();
+        let result = compiler.compile("= Page 1\n#pagebreak()\n= Page 2");
 
         assert!(result.success);
         assert!(!result.document.is_null());
@@ -146,10 +243,9 @@ mod tests {
     fn test_render_single_page_svg() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Test Page\n\nContent here.");
-        let result = compiler.compile();
+        let result = compiler.compile("= Test Page\n\nContent here.");
 
         assert!(result.success);
 
@@ -176,10 +272,9 @@ mod tests {
     fn test_render_page_svg_out_of_bounds() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Single Page Document");
-        let result = compiler.compile();
+        let result = compiler.compile("= Single Page Document");
 
         assert!(result.success);
 
@@ -210,17 +305,16 @@ mod tests {
     fn test_render_all_pages_svg() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Page 1\n#pagebreak()\n= Page 2\n#pagebreak()\n= Page 3");
-        let result = compiler.compile();
+        let result = compiler.compile("= Page 1\n#pagebreak()\n= Page 2\n#pagebreak()\n= Page 3");
 
         assert!(result.success);
 
         unsafe {
             let doc = result.document as *const DocumentInstance;
             let page_count = document_page_count(doc);
-            let array = document_render_all_pages_svg(doc);
+            let array = document_render_all_pages_svg(doc, None);
 
             assert!(!array.buffers.is_null());
             assert_eq!(array.len, page_count);
@@ -246,26 +340,88 @@ mod tests {
     #[test]
     fn test_render_all_pages_svg_null_document() {
         unsafe {
-            let array = document_render_all_pages_svg(ptr::null());
+            let array = document_render_all_pages_svg(ptr::null(), None);
             assert!(array.buffers.is_null());
             assert_eq!(array.len, 0);
         }
     }
 
+    #[test]
+    fn test_render_png() {
+        let temp_dir = env::temp_dir();
+        let options = default_options();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+
+        let result =
+            compiler.compile("#set page(width: 100pt, height: 50pt)\n= A\n#pagebreak()\n= B");
+
+        assert!(result.success);
+
+        unsafe {
+            let doc = result.document as *const DocumentInstance;
+
+            let png_buffer = document_render_page_png(doc, 0, 2.0);
+            assert!(!png_buffer.data.is_null());
+            let png_bytes = std::slice::from_raw_parts(png_buffer.data, png_buffer.len);
+            assert_eq!(&png_bytes[..8], b"\x89PNG\r\n\x1a\n");
+            // IHDR holds width and height as big-endian u32
+            assert_eq!(&png_bytes[16..24], &[0, 0, 0, 200, 0, 0, 0, 100]);
+
+            let array = document_render_all_pages_png(doc, 1.0, None);
+            assert_eq!(array.len, 2);
+
+            let invalid = document_render_page_png(doc, 0, 0.0);
+            assert!(invalid.data.is_null());
+
+            // Clean up
+            crate::memory::free_buffer(png_buffer);
+            crate::memory::free_buffer_array(array);
+            let _ = Box::from_raw(result.document as *mut DocumentInstance);
+            crate::memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
+        }
+    }
+
+    #[test]
+    fn test_font_report() {
+        let temp_dir = env::temp_dir();
+        let options = default_options();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+
+        let result = compiler.compile("Hello 😀");
+        assert!(result.success);
+
+        unsafe {
+            let doc = result.document as *const DocumentInstance;
+            let buffer = document_font_report(doc);
+            let json = std::str::from_utf8(std::slice::from_raw_parts(buffer.data, buffer.len));
+            let report: serde_json::Value = serde_json::from_str(json.unwrap()).unwrap();
+
+            assert_eq!(report["fonts"][0]["pages"], serde_json::json!([0]));
+            assert!(report["fonts"][0]["weight"].is_u64());
+            assert!(report["missing_glyphs"].is_array());
+
+            assert!(document_font_report(ptr::null()).data.is_null());
+
+            // Clean up
+            crate::memory::free_buffer(buffer);
+            let _ = Box::from_raw(result.document as *mut DocumentInstance);
+            crate::memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
+        }
+    }
+
     #[test]
     fn test_render_pdf() {
         let temp_dir = env::temp_dir();
         let options = default_options();
-        let mut compiler = CompilerInstance::new(temp_dir, &options).unwrap();
+        let compiler = CompilerInstance::new(temp_dir, &options).unwrap();
 
-        compiler.update_source("= Test");
-        let result = compiler.compile();
+        let result = compiler.compile("= Test");
 
         assert!(result.success);
 
         unsafe {
             let doc = result.document as *const DocumentInstance;
-            let pdf_buffer = document_render_pdf(doc);
+            let pdf_buffer = document_render_pdf(doc, None);
 
             assert!(!pdf_buffer.data.is_null());
             assert!(pdf_buffer.len > 0);
@@ -1,20 +1,34 @@
 // FFI SURFACE: C-Compatible exports for .NET interop
 // This is the only public API surface.
 
+mod cancel;
+mod compile_pool;
 mod compiler;
 mod document;
 mod memory;
 #[allow(non_camel_case_types)]
 mod types;
 mod typst_backend;
+mod watch;
 
 use std::path::PathBuf;
 use std::ptr;
 use std::slice;
+use std::sync::Arc;
 
 use crate::types::CompilerOptions;
-use compiler::{CompilerInstance, DocumentInstance};
-use types::{Buffer, BufferArray, CompileResult};
+use cancel::CancellationToken;
+use compiler::{CompilerInstance, DocumentInstance, FontStoreInstance, check_source_syntax};
+use types::{Buffer, BufferArray, CompileResult, CompileResultArray, ErrorKind};
+use watch::WatchSession;
+
+/// Rust access to the core for the `typst-net` command-line tool.
+/// Not a stable API; hosts should use the C exports below.
+#[doc(hidden)]
+pub mod native {
+    pub use crate::compiler::{CompilerInstance, DocumentInstance};
+    pub use crate::types::{CompileResult, CompilerOptions, Diagnostic, DiagnosticSeverity};
+}
 // ============================================================================
 // VERSION INFORMATION
 // ============================================================================
@@ -32,6 +46,21 @@ pub extern "C" fn typst_net_version_len() -> usize {
     TYPST_NET_VERSION.len()
 }
 
+/// Version of the binary interface (exports and struct layouts)
+///
+/// Bumped when an export or struct changes incompatibly. Appending fields to
+/// `CompilerOptions` does not bump it; `struct_size` covers those.
+pub const TYPST_NET_ABI_VERSION: u32 = 1;
+
+/// Get the ABI version of the loaded library
+///
+/// Hosts should compare it with the `TYPST_NET_ABI_VERSION` they were built
+/// against and refuse to use the library when they differ.
+#[unsafe(no_mangle)]
+pub extern "C" fn typst_net_abi_version() -> u32 {
+    TYPST_NET_ABI_VERSION
+}
+
 // ============================================================================
 // COMPILER LIFECYCLE
 // ============================================================================
@@ -44,10 +73,31 @@ pub extern "C" fn typst_net_version_len() -> usize {
 /// * `options` - Compiler configuration options (can be null for defaults)
 ///
 /// # Options fields (all optional, pass null struct for defaults):
+/// * `struct_size` - Required: `sizeof(CompilerOptions)` as the caller knows it. Fields
+///   added in later library versions keep their defaults; too small a size fails
 /// * `include_system_fonts` - Whether to load system fonts (default: true)
+/// * `include_embedded_fonts` - Whether to load the bundled fonts (default: true;
+///   always off when built without the `embed-fonts` feature)
 /// * `inputs_json` - JSON object string of inputs: {"key": "value"}
 /// * `custom_font_paths` - Array of font directory paths (TODO: not yet implemented)
-/// * `package_path` - Path for offline packages
+/// * `package_path` - Path for offline packages, unpacked as `namespace/name/version/`
+///   or archived as `namespace/name-version.tar.gz`
+/// * `package_paths` - JSON array of more package roots, searched after `package_path`
+///   in order (earlier roots override packages of later ones)
+/// * `include_user_packages` - Search the user package directory last, where the typst
+///   CLI keeps `@local` packages (e.g. `~/.local/share/typst/packages`)
+/// * `package_cache_path`, `package_registry_url`, `offline_packages` - Download packages
+///   missing from the package roots into the cache (no cache path = no downloads;
+///   `@local` packages are never downloaded)
+/// * `allowed_extensions`, `max_file_size`, `max_total_read_bytes`, `forbid_filesystem` -
+///   Sandbox policy for files read from disk (zero/empty = unrestricted)
+/// * `max_compile_time_ms`, `max_pages`, `max_output_bytes` - Resource limits (0 = unlimited).
+///   A compilation past its time limit is abandoned: the call returns, but typst keeps
+///   its CPU core busy until it finishes (see `typst_net_abandoned_compiles`). The page
+///   count is checked once layout finished
+/// * `use_fixed_date`, `fixed_date_unix_seconds` - Fixed current date for reproducible output
+///   (falls back to `SOURCE_DATE_EPOCH`, then the system clock)
+/// * `render_threads` - Threads used to render pages in parallel (0 = one per CPU core)
 ///
 /// # Returns
 /// Opaque pointer to compiler instance, or null on failure
@@ -66,6 +116,52 @@ pub unsafe extern "C" fn typst_net_compiler_create(
     root_path: *const u8,
     root_path_len: usize,
     options: *const CompilerOptions,
+) -> *mut std::ffi::c_void {
+    unsafe { create_compiler(root_path, root_path_len, options, None) }
+}
+
+/// Create a new compiler instance that uses a shared font store
+///
+/// Skips the font search, so creating short-lived compilers is cheap.
+/// The font fields of `options` (`include_system_fonts`, `include_embedded_fonts`,
+/// `custom_font_paths`) are ignored.
+///
+/// # Arguments
+/// * `root_path` - UTF-8 encoded path to workspace root
+/// * `root_path_len` - Length of root_path in bytes
+/// * `options` - Compiler configuration options (can be null for defaults)
+/// * `font_store` - Store from `typst_net_font_store_create`
+///
+/// # Returns
+/// Opaque pointer to compiler instance, or null on failure
+///
+/// # Safety
+/// * Same requirements as `typst_net_compiler_create`
+/// * `font_store` must be a valid pointer from `typst_net_font_store_create`.
+///   It may be freed while the compiler is still in use.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_create_with_fonts(
+    root_path: *const u8,
+    root_path_len: usize,
+    options: *const CompilerOptions,
+    font_store: *const std::ffi::c_void,
+) -> *mut std::ffi::c_void {
+    if font_store.is_null() {
+        return ptr::null_mut();
+    }
+
+    unsafe {
+        let font_store = &*(font_store as *const FontStoreInstance);
+        create_compiler(root_path, root_path_len, options, Some(font_store))
+    }
+}
+
+/// Shared implementation of the compiler create exports
+unsafe fn create_compiler(
+    root_path: *const u8,
+    root_path_len: usize,
+    options: *const CompilerOptions,
+    font_store: Option<&FontStoreInstance>,
 ) -> *mut std::ffi::c_void {
     if root_path.is_null() || root_path_len == 0 {
         return ptr::null_mut();
@@ -80,13 +176,16 @@ pub unsafe extern "C" fn typst_net_compiler_create(
 
         let root = PathBuf::from(path_str);
 
-        let opts = if options.is_null() {
-            CompilerOptions::default()
-        } else {
-            *options
+        let Some(opts) = CompilerOptions::read(options) else {
+            return ptr::null_mut();
+        };
+
+        let compiler = match font_store {
+            Some(font_store) => CompilerInstance::with_font_store(root, &opts, font_store),
+            None => CompilerInstance::new(root, &opts),
         };
 
-        match CompilerInstance::new(root, &opts) {
+        match compiler {
             Ok(compiler) => Box::into_raw(Box::new(compiler)) as *mut std::ffi::c_void,
             Err(_) => ptr::null_mut(),
         }
@@ -95,6 +194,8 @@ pub unsafe extern "C" fn typst_net_compiler_create(
 
 /// Free a compiler instance
 ///
+/// Compilations running on other threads must have finished.
+///
 /// # Safety
 /// - `compiler` must be a valid pointer from `typst_net_compiler_create`
 /// - `compiler` must not be used after this call
@@ -108,19 +209,173 @@ pub unsafe extern "C" fn typst_net_compiler_free(compiler: *mut std::ffi::c_void
     }
 }
 
+/// Replace the inputs (`sys.inputs`) of a compiler
+///
+/// Only the standard library is rebuilt; loaded fonts are reused, so one
+/// compiler can serve many documents that differ only in their inputs.
+///
+/// # Arguments
+/// * `compiler` - Valid compiler pointer
+/// * `inputs_json` - JSON object string of inputs: {"key": "value"} (null clears all inputs)
+/// * `inputs_json_len` - Length of inputs_json in bytes
+///
+/// # Returns
+/// true on success; false on invalid JSON, leaving the previous inputs in place.
+/// Compilations already running keep the inputs they started with.
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+/// - `inputs_json` must be null or valid UTF-8
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_set_inputs(
+    compiler: *mut std::ffi::c_void,
+    inputs_json: *const u8,
+    inputs_json_len: usize,
+) -> bool {
+    if compiler.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let compiler = &*(compiler as *const CompilerInstance);
+
+        let json = if inputs_json.is_null() || inputs_json_len == 0 {
+            None
+        } else {
+            match std::str::from_utf8(slice::from_raw_parts(inputs_json, inputs_json_len)) {
+                Ok(s) => Some(s),
+                Err(_) => return false,
+            }
+        };
+
+        compiler.set_inputs(json).is_ok()
+    }
+}
+
+// ============================================================================
+// FONTS
+// ============================================================================
+
+/// Search fonts once for use by many compilers
+///
+/// Only the font options (`include_system_fonts`, `include_embedded_fonts`,
+/// `custom_font_paths`) of `options` are used.
+///
+/// # Returns
+/// Opaque font store pointer, or null on failure (including a too small `struct_size`).
+/// Caller must free with `typst_net_font_store_free`
+///
+/// # Safety
+/// * `options` must be null or valid during this call (borrowed, like `typst_net_compiler_create`)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_font_store_create(
+    options: *const CompilerOptions,
+) -> *mut std::ffi::c_void {
+    let Some(opts) = (unsafe { CompilerOptions::read(options) }) else {
+        return ptr::null_mut();
+    };
+
+    match FontStoreInstance::new(&opts) {
+        Ok(store) => Box::into_raw(Box::new(store)) as *mut std::ffi::c_void,
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a font store
+///
+/// Compilers created from the store keep working; they hold their own reference.
+///
+/// # Safety
+/// - `font_store` must be a valid pointer from `typst_net_font_store_create`
+/// - Must only be called once per store
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_font_store_free(font_store: *mut std::ffi::c_void) {
+    if !font_store.is_null() {
+        unsafe {
+            let _ = Box::from_raw(font_store as *mut FontStoreInstance);
+        }
+    }
+}
+
+/// Add fonts from memory to a compiler
+///
+/// Parses a font file or collection (TTF, OTF, TTC, OTC) and makes every face
+/// in it available to subsequent compilations of this compiler only.
+///
+/// # Arguments
+/// * `compiler` - Valid compiler pointer
+/// * `data` - Font file bytes (copied; caller retains ownership)
+/// * `data_len` - Length of data in bytes
+///
+/// # Returns
+/// Number of font faces added, 0 if the data is not a valid font
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+/// - `data` must point to `data_len` readable bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_add_font(
+    compiler: *mut std::ffi::c_void,
+    data: *const u8,
+    data_len: usize,
+) -> usize {
+    if compiler.is_null() || data.is_null() || data_len == 0 {
+        return 0;
+    }
+
+    unsafe {
+        let compiler = &*(compiler as *const CompilerInstance);
+        compiler.add_font(slice::from_raw_parts(data, data_len).to_vec())
+    }
+}
+
+/// List every font available to a compiler as JSON
+///
+/// Fonts are listed in lookup order. Example entry:
+/// `{"family": "Inter", "style": "normal", "weight": 400, "stretch": 1.0,
+///   "source": "custom", "path": "/fonts/Inter.ttf", "collection_index": 0,
+///   "coverage": {"codepoints": 2548, "ranges": [[32, 126], ...]}}`
+///
+/// `source` is "embedded", "system", "custom" or "memory"; `path` is null
+/// unless the font was loaded from a file.
+///
+/// # Returns
+/// Buffer containing a UTF-8 JSON array - caller must free with `typst_net_buffer_free`.
+/// Empty buffer if `compiler` is null.
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_list_fonts(
+    compiler: *const std::ffi::c_void,
+) -> Buffer {
+    if compiler.is_null() {
+        return Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+    }
+
+    let compiler = unsafe { &*(compiler as *const CompilerInstance) };
+    memory::string_to_buffer(compiler.list_fonts_json())
+}
+
 // ============================================================================
 // COMPILATION
 // ============================================================================
 
 /// Compile typst source code
 ///
+/// Thread-safe: one compiler can run many compilations concurrently.
+/// Each call compiles its own source; fonts and library are shared.
+///
 /// # Arguments
 /// * `compiler` - Valid compiler pointer
 /// * `source` - UTF-8 encoded source code
 /// * `source_len` - Length of source in bytes
 ///
 /// # Returns
-/// CompileResult - caller must free with `typst_net_compile_result_free`
+/// CompileResult - caller must free with `typst_net_result_free`
 ///
 /// # Safety
 /// - `compiler` must be a valid pointer from `typst_net_compiler_create`
@@ -130,18 +385,58 @@ pub unsafe extern "C" fn typst_net_compiler_compile(
     compiler: *mut std::ffi::c_void,
     source: *const u8,
     source_len: usize,
+) -> CompileResult {
+    unsafe { compile_source(compiler, source, source_len, None) }
+}
+
+/// Compile typst source code, aborting once a cancellation token is triggered
+///
+/// The call returns within milliseconds of the token being triggered, at any
+/// stage of evaluation or layout. typst itself can't be interrupted, so this
+/// releases the caller, not the CPU: the abandoned compilation keeps running
+/// on a pool thread until it finishes, failing its file reads (see
+/// `typst_net_abandoned_compiles`).
+/// A cancelled compilation returns `success = false` with `error_kind = Cancelled`.
+///
+/// # Arguments
+/// * `compiler` - Valid compiler pointer
+/// * `source` - UTF-8 encoded source code
+/// * `source_len` - Length of source in bytes
+/// * `cancel_token` - Token from `typst_net_cancel_token_create` (can be null)
+///
+/// # Returns
+/// CompileResult - caller must free with `typst_net_result_free`
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+/// - `source` must be valid UTF-8
+/// - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_compile_with_cancel(
+    compiler: *mut std::ffi::c_void,
+    source: *const u8,
+    source_len: usize,
+    cancel_token: *const std::ffi::c_void,
+) -> CompileResult {
+    unsafe {
+        let cancel = CancellationToken::clone_from_raw(cancel_token);
+        compile_source(compiler, source, source_len, cancel)
+    }
+}
+
+/// Shared implementation of the compile exports
+unsafe fn compile_source(
+    compiler: *mut std::ffi::c_void,
+    source: *const u8,
+    source_len: usize,
+    cancel: Option<Arc<CancellationToken>>,
 ) -> CompileResult {
     if compiler.is_null() {
-        return CompileResult {
-            success: false,
-            diagnostics: ptr::null_mut(),
-            diagnostics_len: 0,
-            document: ptr::null_mut(),
-        };
+        return CompileResult::default();
     }
 
     unsafe {
-        let compiler = &mut *(compiler as *mut CompilerInstance);
+        let compiler = &*(compiler as *const CompilerInstance);
 
         let source_str = if source.is_null() || source_len == 0 {
             "" // empty source is valid
@@ -150,100 +445,612 @@ pub unsafe extern "C" fn typst_net_compiler_compile(
             match std::str::from_utf8(source_bytes) {
                 Ok(s) => s,
                 Err(_) => {
-                    return CompileResult {
-                        success: false,
-                        diagnostics: ptr::null_mut(),
-                        diagnostics_len: 0,
-                        document: ptr::null_mut(),
-                    };
+                    return CompileResult::default();
                 }
             }
         };
 
-        compiler.update_source(source_str);
-        compiler.compile()
+        match cancel {
+            Some(token) => compiler.compile_with_cancel(source_str, token),
+            None => compiler.compile(source_str),
+        }
+    }
+}
+
+/// Compile one template for many input sets (mail merge)
+///
+/// Each entry of `inputs_json_array` replaces `sys.inputs` for one compilation.
+/// Entries are compiled in parallel and share fonts and caches. The compiler's
+/// own inputs are not changed.
+///
+/// # Arguments
+/// * `compiler` - Valid compiler pointer
+/// * `source` - UTF-8 encoded template source code
+/// * `source_len` - Length of source in bytes
+/// * `inputs_json_array` - JSON array of input objects: [{"name": "A"}, {"name": "B"}]
+/// * `inputs_json_array_len` - Length of inputs_json_array in bytes
+///
+/// # Returns
+/// One CompileResult per entry, in input order. Entries that are not objects
+/// (or null) yield a failed result. An empty JSON array yields an empty array
+/// with a non-null `results` pointer; null array on invalid arguments or JSON.
+/// Caller must free with `typst_net_result_array_free`
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+/// - `source` and `inputs_json_array` must be valid UTF-8
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_compile_batch(
+    compiler: *mut std::ffi::c_void,
+    source: *const u8,
+    source_len: usize,
+    inputs_json_array: *const u8,
+    inputs_json_array_len: usize,
+) -> CompileResultArray {
+    unsafe {
+        compile_batch_source(
+            compiler,
+            source,
+            source_len,
+            inputs_json_array,
+            inputs_json_array_len,
+            None,
+        )
+    }
+}
+
+/// Compile one template for many input sets with cancellation support
+///
+/// Same as `typst_net_compiler_compile_batch`, but once `cancel_token` is
+/// triggered every entry still running is abandoned with
+/// `ErrorKind::Cancelled` and entries not started yet are skipped. Like
+/// `typst_net_compiler_compile_with_cancel`, this releases the caller, not
+/// the CPU (see `typst_net_abandoned_compiles`).
+///
+/// # Arguments
+/// * `compiler` - Valid compiler pointer
+/// * `source` - UTF-8 encoded template source code
+/// * `source_len` - Length of source in bytes
+/// * `inputs_json_array` - JSON array of input objects
+/// * `inputs_json_array_len` - Length of inputs_json_array in bytes
+/// * `cancel_token` - Token from `typst_net_cancel_token_create` (can be null)
+///
+/// # Returns
+/// One CompileResult per entry, in input order (see `typst_net_compiler_compile_batch`).
+/// Caller must free with `typst_net_result_array_free`
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+/// - `source` and `inputs_json_array` must be valid UTF-8
+/// - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_compile_batch_with_cancel(
+    compiler: *mut std::ffi::c_void,
+    source: *const u8,
+    source_len: usize,
+    inputs_json_array: *const u8,
+    inputs_json_array_len: usize,
+    cancel_token: *const std::ffi::c_void,
+) -> CompileResultArray {
+    unsafe {
+        let cancel = CancellationToken::clone_from_raw(cancel_token);
+        compile_batch_source(
+            compiler,
+            source,
+            source_len,
+            inputs_json_array,
+            inputs_json_array_len,
+            cancel,
+        )
+    }
+}
+
+/// Shared implementation of the batch compile exports
+unsafe fn compile_batch_source(
+    compiler: *mut std::ffi::c_void,
+    source: *const u8,
+    source_len: usize,
+    inputs_json_array: *const u8,
+    inputs_json_array_len: usize,
+    cancel: Option<Arc<CancellationToken>>,
+) -> CompileResultArray {
+    let empty = CompileResultArray {
+        results: ptr::null_mut(),
+        len: 0,
+    };
+
+    if compiler.is_null() || inputs_json_array.is_null() || inputs_json_array_len == 0 {
+        return empty;
+    }
+
+    unsafe {
+        let compiler = &*(compiler as *const CompilerInstance);
+
+        let source_str = if source.is_null() || source_len == 0 {
+            ""
+        } else {
+            match std::str::from_utf8(slice::from_raw_parts(source, source_len)) {
+                Ok(s) => s,
+                Err(_) => return empty,
+            }
+        };
+
+        let inputs_str = match std::str::from_utf8(slice::from_raw_parts(
+            inputs_json_array,
+            inputs_json_array_len,
+        )) {
+            Ok(s) => s,
+            Err(_) => return empty,
+        };
+
+        match compiler.compile_batch(source_str, inputs_str, cancel) {
+            Ok(results) => memory::results_to_array(results),
+            Err(_) => empty,
+        }
+    }
+}
+
+/// List the files the compiler's last finished compilation read from disk
+///
+/// Covers workspace and package files accessed through `typst_net_compiler_compile`
+/// and `typst_net_compiler_compile_with_cancel`; batch compiles don't update it.
+/// Example: `["/work/chapter.typ", "/work/logo.png", "/data/packages/preview/cetz-0.3.0.tar.gz"]`
+///
+/// Paths are canonical, in order of first request. Files that were missing or
+/// could not be read are included, as creating or fixing them changes the
+/// output. Package files loaded from an archive are reported as the archive.
+/// The main source is not included.
+///
+/// # Returns
+/// Buffer containing a UTF-8 JSON array - caller must free with `typst_net_buffer_free`.
+/// Empty buffer if `compiler` is null.
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_compiler_dependencies(
+    compiler: *const std::ffi::c_void,
+) -> Buffer {
+    if compiler.is_null() {
+        return Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+    }
+
+    let compiler = unsafe { &*(compiler as *const CompilerInstance) };
+    memory::string_to_buffer(compiler.dependencies_json())
+}
+
+/// Check typst source code for syntax errors only
+///
+/// Parses the source without creating a compiler, loading fonts or touching
+/// the filesystem. Semantic errors (unknown variables, missing files, ...)
+/// are not reported.
+///
+/// # Arguments
+/// * `source` - UTF-8 encoded source code
+/// * `source_len` - Length of source in bytes
+///
+/// # Returns
+/// CompileResult with syntax errors as diagnostics and a null document.
+/// Caller must free with `typst_net_result_free`
+///
+/// # Safety
+/// - `source` must be valid UTF-8
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_check_syntax(
+    source: *const u8,
+    source_len: usize,
+) -> CompileResult {
+    let source_str = if source.is_null() || source_len == 0 {
+        "" // empty source is valid
+    } else {
+        let source_bytes = unsafe { slice::from_raw_parts(source, source_len) };
+        match std::str::from_utf8(source_bytes) {
+            Ok(s) => s,
+            Err(_) => return CompileResult::default(),
+        }
+    };
+
+    check_source_syntax(source_str)
+}
+
+/// Free a compilation result
+///
+/// # Safety
+/// - `result` must be from a `typst_net_compiler_compile` call
+/// - must only be called once per result
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_result_free(result: CompileResult) {
+    unsafe {
+        // Free diagnostics
+        if !result.diagnostics.is_null() && result.diagnostics_len > 0 {
+            memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
+        }
+
+        // Free document if present
+        if !result.document.is_null() {
+            let _ = Box::from_raw(result.document as *mut DocumentInstance);
+        }
+    }
+}
+
+/// Free an array of compilation results, including every document in it
+///
+/// # Safety
+/// - `array` must be from `typst_net_compiler_compile_batch` or
+///   `typst_net_compiler_compile_batch_with_cancel`
+/// - Documents in the array must not be used after this call
+/// - Must only be called once per array
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_result_array_free(array: CompileResultArray) {
+    unsafe {
+        for result in memory::take_results(array) {
+            typst_net_result_free(result);
+        }
+    }
+}
+
+// ============================================================================
+// CANCELLATION
+// ============================================================================
+
+/// Create a cancellation token
+///
+/// Pass the token to `*_with_cancel` functions, then call
+/// `typst_net_cancel_token_cancel` from any thread to stop them.
+///
+/// # Returns
+/// Opaque token pointer - caller must free with `typst_net_cancel_token_free`
+#[unsafe(no_mangle)]
+pub extern "C" fn typst_net_cancel_token_create() -> *mut std::ffi::c_void {
+    CancellationToken::into_raw(Arc::new(CancellationToken::default()))
+}
+
+/// Trigger a cancellation token
+///
+/// Safe to call from any thread, including while the token is in use.
+/// Cancellation is permanent; create a new token for the next operation.
+///
+/// # Safety
+/// - `token` must be a valid pointer from `typst_net_cancel_token_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_cancel_token_cancel(token: *const std::ffi::c_void) {
+    if let Some(token) = unsafe { (token as *const CancellationToken).as_ref() } {
+        token.cancel();
+    }
+}
+
+/// Free a cancellation token
+///
+/// A compilation still using the token keeps its own reference,
+/// so freeing here never invalidates an in-flight operation.
+///
+/// # Safety
+/// - `token` must be a valid pointer from `typst_net_cancel_token_create`
+/// - Must only be called once per token
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_cancel_token_free(token: *mut std::ffi::c_void) {
+    unsafe { CancellationToken::free_raw(token) }
+}
+
+/// Number of compilations abandoned at their time limit or on cancellation
+/// that are still queued or running
+///
+/// typst can't be interrupted, so an abandoned compilation keeps its thread
+/// and CPU core busy until it finishes. Compilations with a time limit or
+/// cancellation token run on a pool of one thread per core (at least 4);
+/// while abandoned ones fill it, new ones queue and fail at their own limit.
+/// Hosts compiling untrusted templates can recycle the process when this
+/// count stays high.
+#[unsafe(no_mangle)]
+pub extern "C" fn typst_net_abandoned_compiles() -> usize {
+    compile_pool::abandoned_jobs()
+}
+
+// ============================================================================
+// WATCH MODE
+// ============================================================================
+
+/// Compile a source and recompile it whenever a file it depends on changes
+///
+/// Compiles right away, then watches the workspace root and the package
+/// directories the last compilation used with filesystem notifications.
+/// Changes to files the last compilation requested trigger a recompilation;
+/// after a failed compilation any change does. Bursts of changes are coalesced.
+///
+/// Every result is passed to `callback(result, user_data)` on a background
+/// thread. The callback owns the result and must free it with
+/// `typst_net_result_free`.
+///
+/// # Arguments
+/// * `compiler` - Compiler to compile with (may be used concurrently by other calls)
+/// * `source` / `source_len` - UTF-8 main source, copied
+/// * `callback` - Receives each compile result
+/// * `user_data` - Passed through to `callback`
+///
+/// # Returns
+/// Opaque watch handle - stop with `typst_net_watch_stop`.
+/// Null if an argument is invalid or watching failed.
+///
+/// # Safety
+/// - `compiler` must be a valid pointer from `typst_net_compiler_create` and
+///   must not be freed before the watch is stopped
+/// - `source` must point to `source_len` bytes
+/// - `callback` and `user_data` must be safe to use from another thread
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_watch_start(
+    compiler: *const std::ffi::c_void,
+    source: *const u8,
+    source_len: usize,
+    // Spelled out (not `Option<WatchCallback>`) so the C header gets a nullable function pointer
+    callback: Option<unsafe extern "C" fn(result: CompileResult, user_data: *mut std::ffi::c_void)>,
+    user_data: *mut std::ffi::c_void,
+) -> *mut std::ffi::c_void {
+    let Some(callback) = callback else {
+        return ptr::null_mut();
+    };
+    if compiler.is_null() || source.is_null() {
+        return ptr::null_mut();
+    }
+
+    unsafe {
+        let source_bytes = slice::from_raw_parts(source, source_len);
+        let Ok(source_str) = std::str::from_utf8(source_bytes) else {
+            return ptr::null_mut();
+        };
+
+        match WatchSession::start(
+            compiler as *const CompilerInstance,
+            source_str.to_string(),
+            callback,
+            user_data,
+        ) {
+            Ok(session) => Box::into_raw(Box::new(session)) as *mut std::ffi::c_void,
+            Err(_) => ptr::null_mut(),
+        }
+    }
+}
+
+/// Stop a watch
+///
+/// Cancels a compilation in progress and blocks until its (cancelled) result
+/// has been delivered; no callback runs after this returns.
+///
+/// # Safety
+/// - `watch` must be a valid pointer from `typst_net_watch_start`
+/// - Must only be called once per watch, and never from inside the callback
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_watch_stop(watch: *mut std::ffi::c_void) {
+    if !watch.is_null() {
+        unsafe {
+            drop(Box::from_raw(watch as *mut WatchSession));
+        }
+    }
+}
+
+// ============================================================================
+// DOCUMENT OPERATIONS
+// ============================================================================
+
+/// Get the number of pages in a document
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_page_count(document: *const std::ffi::c_void) -> usize {
+    unsafe { document::document_page_count(document as *const DocumentInstance) }
+}
+
+/// Render a single page to SVG
+///
+/// # Arguments
+/// * `document` - Valid document pointer
+/// * `page_index` - 0-indexed page number
+///
+/// # Returns
+/// Buffer containing SVG data - caller must free with `typst_net_buffer_free`
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+/// - `page_index` must be < page_count
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_render_svg_page(
+    document: *const std::ffi::c_void,
+    page_index: usize,
+) -> Buffer {
+    unsafe { document::document_render_page_svg(document as *const DocumentInstance, page_index) }
+}
+
+/// Render all pages to SVG
+///
+/// # Returns
+/// BufferArray containing SVG data for each page - caller must free with `typst_net_buffer_array_free`
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_render_svg_all(
+    document: *const std::ffi::c_void,
+) -> BufferArray {
+    unsafe { document::document_render_all_pages_svg(document as *const DocumentInstance, None) }
+}
+
+/// Render all pages to SVG, checking a cancellation token before each page
+///
+/// # Returns
+/// BufferArray containing SVG data for each page, or an empty array if cancelled
+/// (`typst_net_document_last_error` then reports `Cancelled`)
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+/// - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_render_svg_all_with_cancel(
+    document: *const std::ffi::c_void,
+    cancel_token: *const std::ffi::c_void,
+) -> BufferArray {
+    unsafe {
+        let cancel = (cancel_token as *const CancellationToken).as_ref();
+        document::document_render_all_pages_svg(document as *const DocumentInstance, cancel)
+    }
+}
+
+/// Render a single page to PNG
+///
+/// # Arguments
+/// * `document` - Valid document pointer
+/// * `page_index` - Zero-based page index
+/// * `pixels_per_pt` - Resolution (1.0 = 72 DPI, 2.0 = 144 DPI)
+///
+/// # Returns
+/// Buffer containing PNG data - caller must free with `typst_net_buffer_free`
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+/// - `page_index` must be < page_count
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_render_png_page(
+    document: *const std::ffi::c_void,
+    page_index: usize,
+    pixels_per_pt: f32,
+) -> Buffer {
+    unsafe {
+        document::document_render_page_png(
+            document as *const DocumentInstance,
+            page_index,
+            pixels_per_pt,
+        )
+    }
+}
+
+/// Render all pages to PNG, in parallel on `render_threads` threads
+///
+/// # Returns
+/// BufferArray containing PNG data for each page - caller must free with `typst_net_buffer_array_free`
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_render_png_all(
+    document: *const std::ffi::c_void,
+    pixels_per_pt: f32,
+) -> BufferArray {
+    unsafe {
+        document::document_render_all_pages_png(
+            document as *const DocumentInstance,
+            pixels_per_pt,
+            None,
+        )
     }
 }
 
-/// Free a compilation result
+/// Render all pages to PNG, checking a cancellation token before each page
+///
+/// # Returns
+/// BufferArray containing PNG data for each page, or an empty array if cancelled
+/// (`typst_net_document_last_error` then reports `Cancelled`)
 ///
 /// # Safety
-/// - `result` must be from a `typst_net_compiler_compile` call
-/// - must only be called once per result
+/// - `document` must be a valid pointer from a successful CompileResult
+/// - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn typst_net_result_free(result: CompileResult) {
+pub unsafe extern "C" fn typst_net_document_render_png_all_with_cancel(
+    document: *const std::ffi::c_void,
+    pixels_per_pt: f32,
+    cancel_token: *const std::ffi::c_void,
+) -> BufferArray {
     unsafe {
-        // Free diagnostics
-        if !result.diagnostics.is_null() && result.diagnostics_len > 0 {
-            memory::free_diagnostics(result.diagnostics, result.diagnostics_len);
-        }
-
-        // Free document if present
-        if !result.document.is_null() {
-            let _ = Box::from_raw(result.document as *mut DocumentInstance);
-        }
+        let cancel = (cancel_token as *const CancellationToken).as_ref();
+        document::document_render_all_pages_png(
+            document as *const DocumentInstance,
+            pixels_per_pt,
+            cancel,
+        )
     }
 }
 
-// ============================================================================
-// DOCUMENT OPERATIONS
-// ============================================================================
+/// Render document to PDF
+///
+/// # Safety
+/// - `document` must be a valid pointer from a successful CompileResult
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn typst_net_document_render_pdf(
+    document: *const std::ffi::c_void,
+) -> Buffer {
+    unsafe { document::document_render_pdf(document as *const DocumentInstance, None) }
+}
 
-/// Get the number of pages in a document
+/// Render document to PDF, checking a cancellation token
 ///
 /// # Safety
 /// - `document` must be a valid pointer from a successful CompileResult
+/// - `cancel_token` must be null or a valid pointer from `typst_net_cancel_token_create`
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn typst_net_document_page_count(document: *const std::ffi::c_void) -> usize {
-    unsafe { document::document_page_count(document as *const DocumentInstance) }
+pub unsafe extern "C" fn typst_net_document_render_pdf_with_cancel(
+    document: *const std::ffi::c_void,
+    cancel_token: *const std::ffi::c_void,
+) -> Buffer {
+    unsafe {
+        let cancel = (cancel_token as *const CancellationToken).as_ref();
+        document::document_render_pdf(document as *const DocumentInstance, cancel)
+    }
 }
 
-/// Render a single page to SVG
+/// Report which fonts a document uses and which characters have no glyph
 ///
-/// # Arguments
-/// * `document` - Valid document pointer
-/// * `page_index` - 0-indexed page number
+/// Characters no loaded font covers are drawn as empty boxes ("tofu");
+/// they are listed in `missing_glyphs`. Example:
+/// `{"fonts": [{"family": "Libertinus Serif", "style": "normal", "weight": 400, "pages": [0, 1]}],
+///   "missing_glyphs": [{"text": "漢", "codepoints": [28450], "font_family": "Libertinus Serif", "pages": [1]}]}`
+/// Page indices are zero-based.
 ///
 /// # Returns
-/// Buffer containing SVG data - caller must free with `typst_net_buffer_free`
+/// Buffer containing UTF-8 JSON - caller must free with `typst_net_buffer_free`
 ///
 /// # Safety
 /// - `document` must be a valid pointer from a successful CompileResult
-/// - `page_index` must be < page_count
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn typst_net_document_render_svg_page(
+pub unsafe extern "C" fn typst_net_document_font_report(
     document: *const std::ffi::c_void,
-    page_index: usize,
 ) -> Buffer {
-    unsafe { document::document_render_page_svg(document as *const DocumentInstance, page_index) }
+    unsafe { document::document_font_report(document as *const DocumentInstance) }
 }
 
-/// Render all pages to SVG
+/// List every package a document was compiled from (a bill of materials)
+///
+/// Example:
+/// `[{"spec": "@preview/cetz:0.3.0", "namespace": "preview", "name": "cetz", "version": "0.3.0",
+///    "path": "/data/packages/preview/cetz/0.3.0", "kind": "directory"}]`
+/// `path` is the resolved package directory or, for `kind` "archive", the `.tar.gz` file.
+/// Packages are listed in order of first use.
 ///
 /// # Returns
-/// BufferArray containing SVG data for each page - caller must free with `typst_net_buffer_array_free`
+/// Buffer containing UTF-8 JSON - caller must free with `typst_net_buffer_free`
 ///
 /// # Safety
 /// - `document` must be a valid pointer from a successful CompileResult
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn typst_net_document_render_svg_all(
-    document: *const std::ffi::c_void,
-) -> BufferArray {
-    unsafe { document::document_render_all_pages_svg(document as *const DocumentInstance) }
+pub unsafe extern "C" fn typst_net_document_packages(document: *const std::ffi::c_void) -> Buffer {
+    unsafe { document::document_packages(document as *const DocumentInstance) }
 }
 
-/// Render document to PDF
+/// Get the reason the last render call on a document failed
 ///
-/// # Note
-/// Currently unimplemented - returns empty buffer
+/// Render functions return an empty buffer on failure; this tells whether
+/// that was caused by the `max_output_bytes` limit or another error.
+///
+/// # Returns
+/// `ErrorKind::None` if the last render succeeded (or nothing was rendered yet)
 ///
 /// # Safety
 /// - `document` must be a valid pointer from a successful CompileResult
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn typst_net_document_render_pdf(
+pub unsafe extern "C" fn typst_net_document_last_error(
     document: *const std::ffi::c_void,
-) -> Buffer {
-    unsafe { document::document_render_pdf(document as *const DocumentInstance) }
+) -> ErrorKind {
+    unsafe { document::document_last_error(document as *const DocumentInstance) }
 }
 
 // ============================================================================
@@ -298,16 +1105,10 @@ mod ffi_tests {
     use crate::types::CompilerOptions;
     use std::ptr;
 
-    //noinspection DuplicatedCode
     fn default_options() -> CompilerOptions {
         CompilerOptions {
             include_system_fonts: true,
-            inputs_json: ptr::null(),
-            inputs_json_len: 0,
-            custom_font_paths: ptr::null(),
-            custom_font_paths_len: 0,
-            package_path: ptr::null(),
-            package_path_len: 0,
+            ..CompilerOptions::default()
         }
     }
 
@@ -324,6 +1125,51 @@ mod ffi_tests {
             let version_str = std::str::from_utf8(version_bytes).unwrap();
             assert!(!version_str.is_empty());
         }
+
+        assert_eq!(typst_net_abi_version(), TYPST_NET_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_options_struct_size_ffi() {
+        /// Options as a newer host would pass them, with a field this library doesn't know
+        #[repr(C)]
+        struct NewerOptions {
+            options: CompilerOptions,
+            future_field: u64,
+        }
+
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+        let create = |options: *const CompilerOptions| unsafe {
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), options);
+            let created = !compiler.is_null();
+            typst_net_compiler_free(compiler);
+            created
+        };
+
+        let mut options = default_options();
+        assert!(create(&options));
+
+        // Older host: only the fields it knew about are read
+        options.struct_size = CompilerOptions::MIN_SIZE;
+        assert!(create(&options));
+
+        // Newer host: unknown trailing fields are ignored
+        let newer = NewerOptions {
+            options: CompilerOptions {
+                struct_size: std::mem::size_of::<NewerOptions>(),
+                ..default_options()
+            },
+            future_field: u64::MAX,
+        };
+        assert!(create(&newer.options));
+
+        // Missing or truncated size
+        options.struct_size = 0;
+        assert!(!create(&options));
+        options.struct_size = CompilerOptions::MIN_SIZE - 1;
+        assert!(!create(&options));
+        assert!(unsafe { typst_net_font_store_create(&options) }.is_null());
     }
 
     #[test]
@@ -411,6 +1257,587 @@ mod ffi_tests {
         }
     }
 
+    #[test]
+    fn test_check_syntax_ffi() {
+        unsafe {
+            let valid = "= Hello\n\n#let x = 1";
+            let result = typst_net_check_syntax(valid.as_ptr(), valid.len());
+            assert!(result.success);
+            assert!(result.document.is_null());
+            typst_net_result_free(result);
+
+            let invalid = "#let x = (unclosed";
+            let result = typst_net_check_syntax(invalid.as_ptr(), invalid.len());
+            assert!(!result.success);
+            assert!(result.diagnostics_len > 0);
+            typst_net_result_free(result);
+
+            let empty = typst_net_check_syntax(ptr::null(), 0);
+            assert!(empty.success);
+            typst_net_result_free(empty);
+        }
+    }
+
+    #[test]
+    fn test_limits_ffi() {
+        unsafe {
+            let root = std::env::temp_dir();
+            let root_str = root.to_str().unwrap();
+            let options = CompilerOptions {
+                max_pages: 1,
+                max_output_bytes: 100,
+                ..default_options()
+            };
+
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options);
+            assert!(!compiler.is_null());
+
+            let source = "= Page 1\n#pagebreak()\n= Page 2";
+            let result = typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+            assert!(!result.success);
+            assert_eq!(result.error_kind, ErrorKind::PageLimitExceeded);
+            typst_net_result_free(result);
+
+            let source = "= Single page";
+            let result = typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+            assert!(result.success);
+            assert_eq!(result.error_kind, ErrorKind::None);
+            assert_eq!(
+                typst_net_document_last_error(result.document),
+                ErrorKind::None
+            );
+
+            let buffer = typst_net_document_render_pdf(result.document);
+            assert!(buffer.data.is_null());
+            assert_eq!(
+                typst_net_document_last_error(result.document),
+                ErrorKind::OutputLimitExceeded
+            );
+
+            let buffer = typst_net_document_render_svg_page(result.document, 5);
+            assert!(buffer.data.is_null());
+            assert_eq!(
+                typst_net_document_last_error(result.document),
+                ErrorKind::Failed
+            );
+
+            typst_net_result_free(result);
+            typst_net_compiler_free(compiler);
+        }
+    }
+
+    #[test]
+    fn test_cancel_token_ffi() {
+        unsafe {
+            let root = std::env::temp_dir();
+            let root_str = root.to_str().unwrap();
+            let options = default_options();
+
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options);
+            let token = typst_net_cancel_token_create();
+            assert!(!token.is_null());
+
+            let source = "= Page 1\n#pagebreak()\n= Page 2";
+            let result = typst_net_compiler_compile_with_cancel(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                token,
+            );
+            assert!(result.success);
+
+            typst_net_cancel_token_cancel(token);
+
+            let array = typst_net_document_render_svg_all_with_cancel(result.document, token);
+            assert!(array.buffers.is_null());
+            assert_eq!(
+                typst_net_document_last_error(result.document),
+                ErrorKind::Cancelled
+            );
+
+            let buffer = typst_net_document_render_pdf_with_cancel(result.document, token);
+            assert!(buffer.data.is_null());
+
+            let cancelled = typst_net_compiler_compile_with_cancel(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                token,
+            );
+            assert!(!cancelled.success);
+            assert_eq!(cancelled.error_kind, ErrorKind::Cancelled);
+
+            // Null token behaves like the plain compile call
+            let plain = typst_net_compiler_compile_with_cancel(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                ptr::null(),
+            );
+            assert!(plain.success);
+
+            typst_net_result_free(plain);
+            typst_net_result_free(cancelled);
+            typst_net_result_free(result);
+            typst_net_cancel_token_free(token);
+            typst_net_compiler_free(compiler);
+        }
+    }
+
+    #[test]
+    fn test_set_inputs_ffi() {
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+        let options = default_options();
+
+        unsafe {
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options);
+            assert!(!compiler.is_null());
+
+            let source = r#"#assert.eq(sys.inputs.customer, "ACME")"#;
+            let result = typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+            assert!(!result.success);
+            typst_net_result_free(result);
+
+            let inputs = r#"{"customer": "ACME"}"#;
+            assert!(typst_net_compiler_set_inputs(
+                compiler,
+                inputs.as_ptr(),
+                inputs.len()
+            ));
+            let result = typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+            assert!(result.success);
+            typst_net_result_free(result);
+
+            let invalid = "[1, 2";
+            assert!(!typst_net_compiler_set_inputs(
+                compiler,
+                invalid.as_ptr(),
+                invalid.len()
+            ));
+            assert!(!typst_net_compiler_set_inputs(
+                ptr::null_mut(),
+                inputs.as_ptr(),
+                inputs.len()
+            ));
+
+            typst_net_compiler_free(compiler);
+        }
+    }
+
+    #[test]
+    fn test_compile_batch_ffi() {
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+        let options = default_options();
+
+        unsafe {
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options);
+            assert!(!compiler.is_null());
+
+            let source = "Dear #sys.inputs.at(\"name\")\n#pagebreak()\nRegards";
+            let inputs = r#"[{"name": "Alice"}, {"name": "Bob"}, 42, {"other": 1}]"#;
+            let array = typst_net_compiler_compile_batch(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                inputs.as_ptr(),
+                inputs.len(),
+            );
+            assert_eq!(array.len, 4);
+
+            let results = slice::from_raw_parts(array.results, array.len);
+            for result in &results[..2] {
+                assert!(result.success);
+                assert_eq!(typst_net_document_page_count(result.document), 2);
+            }
+
+            // Not an object
+            assert!(!results[2].success);
+            assert_eq!(results[2].error_kind, ErrorKind::Failed);
+            // Missing key fails that entry only
+            assert!(!results[3].success);
+            assert!(results[3].diagnostics_len > 0);
+
+            typst_net_result_array_free(array);
+
+            let invalid = "{\"name\": \"Alice\"}";
+            let array = typst_net_compiler_compile_batch(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                invalid.as_ptr(),
+                invalid.len(),
+            );
+            assert!(array.results.is_null());
+            assert_eq!(array.len, 0);
+
+            // An empty batch is not an error
+            let none = "[]";
+            let array = typst_net_compiler_compile_batch(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                none.as_ptr(),
+                none.len(),
+            );
+            assert!(!array.results.is_null());
+            assert_eq!(array.len, 0);
+            typst_net_result_array_free(array);
+
+            let token = typst_net_cancel_token_create();
+            typst_net_cancel_token_cancel(token);
+            let array = typst_net_compiler_compile_batch_with_cancel(
+                compiler,
+                source.as_ptr(),
+                source.len(),
+                inputs.as_ptr(),
+                inputs.len(),
+                token,
+            );
+            assert_eq!(array.len, 4);
+            let results = slice::from_raw_parts(array.results, array.len);
+            assert!(
+                results[..2]
+                    .iter()
+                    .all(|result| result.error_kind == ErrorKind::Cancelled)
+            );
+            typst_net_result_array_free(array);
+            typst_net_cancel_token_free(token);
+
+            typst_net_compiler_free(compiler);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_compile_ffi() {
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+        let options = default_options();
+
+        let compiler =
+            unsafe { typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options) };
+        assert!(!compiler.is_null());
+
+        // Raw pointers are not Send; share the handle as an address
+        let handle = compiler as usize;
+        std::thread::scope(|scope| {
+            for pages in 1..=4 {
+                scope.spawn(move || {
+                    let source = format!("#for _ in range({}) [x #pagebreak(weak: true)]", pages);
+                    unsafe {
+                        let compiler = handle as *mut std::ffi::c_void;
+                        for _ in 0..3 {
+                            let result =
+                                typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+                            assert!(result.success);
+                            assert_eq!(typst_net_document_page_count(result.document), pages);
+                            typst_net_result_free(result);
+                        }
+                    }
+                });
+            }
+        });
+
+        unsafe { typst_net_compiler_free(compiler) };
+    }
+
+    #[test]
+    fn test_font_store_ffi() {
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+        let mut options = default_options();
+        options.include_system_fonts = false;
+
+        unsafe {
+            let store = typst_net_font_store_create(&options);
+            assert!(!store.is_null());
+
+            let first = typst_net_compiler_create_with_fonts(
+                root_str.as_ptr(),
+                root_str.len(),
+                ptr::null(),
+                store,
+            );
+            let second = typst_net_compiler_create_with_fonts(
+                root_str.as_ptr(),
+                root_str.len(),
+                &options,
+                store,
+            );
+            assert!(!first.is_null());
+            assert!(!second.is_null());
+
+            // Compilers outlive the store
+            typst_net_font_store_free(store);
+
+            let source = "= Shared fonts";
+            for compiler in [first, second] {
+                let result = typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+                assert!(result.success);
+                typst_net_result_free(result);
+                typst_net_compiler_free(compiler);
+            }
+
+            let missing = typst_net_compiler_create_with_fonts(
+                root_str.as_ptr(),
+                root_str.len(),
+                &options,
+                ptr::null(),
+            );
+            assert!(missing.is_null());
+        }
+    }
+
+    #[test]
+    fn test_add_font_ffi() {
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+
+        unsafe {
+            let compiler =
+                typst_net_compiler_create(root_str.as_ptr(), root_str.len(), ptr::null());
+            assert!(!compiler.is_null());
+
+            // Valid fonts are covered by the backend tests
+            let garbage = [0u8; 64];
+            assert_eq!(
+                typst_net_compiler_add_font(compiler, garbage.as_ptr(), garbage.len()),
+                0
+            );
+            assert_eq!(typst_net_compiler_add_font(compiler, ptr::null(), 0), 0);
+
+            typst_net_compiler_free(compiler);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "embed-fonts")]
+    fn test_list_fonts_ffi() {
+        let root = std::env::temp_dir();
+        let root_str = root.to_str().unwrap();
+        let mut options = default_options();
+        options.include_system_fonts = false;
+
+        unsafe {
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options);
+            assert!(!compiler.is_null());
+
+            let buffer = typst_net_compiler_list_fonts(compiler);
+            let json = std::str::from_utf8(slice::from_raw_parts(buffer.data, buffer.len)).unwrap();
+            let fonts: serde_json::Value = serde_json::from_str(json).unwrap();
+
+            let fonts = fonts.as_array().unwrap();
+            assert!(!fonts.is_empty());
+            for font in fonts {
+                assert_eq!(font["source"], "embedded");
+                assert!(font["path"].is_null());
+                assert!(font["family"].is_string());
+                assert!(font["weight"].as_u64().unwrap() >= 100);
+                assert!(font["coverage"]["codepoints"].as_u64().unwrap() > 0);
+            }
+
+            typst_net_buffer_free(buffer);
+
+            let empty = typst_net_compiler_list_fonts(ptr::null());
+            assert!(empty.data.is_null());
+
+            typst_net_compiler_free(compiler);
+        }
+    }
+
+    #[test]
+    fn test_compiler_dependencies_ffi() {
+        let root = std::env::temp_dir().join("typst_ffi_dependencies_test");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("chapter.typ"), "#let title = [Chapter]").unwrap();
+        std::fs::write(root.join("data.json"), "{\"value\": 1}").unwrap();
+        let root = root.canonicalize().unwrap();
+        let root_str = root.to_str().unwrap();
+        let options = default_options();
+
+        let source = "#import \"chapter.typ\": title\n#title #json(\"data.json\").value #json(\"data.json\")";
+
+        unsafe {
+            let compiler = typst_net_compiler_create(root_str.as_ptr(), root_str.len(), &options);
+            assert!(!compiler.is_null());
+
+            let read_deps = |compiler| {
+                let buffer = typst_net_compiler_dependencies(compiler);
+                let json = std::str::from_utf8(slice::from_raw_parts(buffer.data, buffer.len))
+                    .unwrap()
+                    .to_string();
+                typst_net_buffer_free(buffer);
+                serde_json::from_str::<serde_json::Value>(&json).unwrap()
+            };
+
+            assert_eq!(read_deps(compiler), serde_json::json!([]));
+
+            let result = typst_net_compiler_compile(compiler, source.as_ptr(), source.len());
+            assert!(result.success);
+            typst_net_result_free(result);
+
+            assert_eq!(
+                read_deps(compiler),
+                serde_json::json!([
+                    root.join("chapter.typ").display().to_string(),
+                    root.join("data.json").display().to_string(),
+                ])
+            );
+
+            let result = typst_net_compiler_compile(compiler, "Plain".as_ptr(), 5);
+            typst_net_result_free(result);
+            assert_eq!(read_deps(compiler), serde_json::json!([]));
+
+            let missing = "#include \"missing.typ\"";
+            let result = typst_net_compiler_compile(compiler, missing.as_ptr(), missing.len());
+            assert!(!result.success);
+            typst_net_result_free(result);
+            assert_eq!(
+                read_deps(compiler),
+                serde_json::json!([root.join("missing.typ").display().to_string()])
+            );
+
+            assert!(typst_net_compiler_dependencies(ptr::null()).data.is_null());
+
+            typst_net_compiler_free(compiler);
+        }
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    /// Compile `include/typst_net.h` against the Rust layout of every repr(C)
+    /// type, so a header that no longer matches the library fails here
+    #[test]
+    fn test_c_header_layout() {
+        use crate::types::{BufferArray, CompileResultArray, Diagnostic, SourceLocation};
+        use std::mem::{offset_of, size_of};
+        use std::process::Command;
+        use types::DiagnosticSeverity;
+
+        let mut checks = String::from("#include <stddef.h>\n#include \"typst_net.h\"\n");
+        checks += &format!(
+            "_Static_assert(TYPST_NET_ABI_VERSION == {}, \"ABI version\");\n",
+            TYPST_NET_ABI_VERSION
+        );
+        macro_rules! layout {
+            ($ty:ident { $($field:ident),* }) => {
+                checks += &format!(
+                    "_Static_assert(sizeof(TypstNet{}) == {}, \"size of {0}\");\n",
+                    stringify!($ty),
+                    size_of::<$ty>()
+                );
+                $(checks += &format!(
+                    "_Static_assert(offsetof(TypstNet{}, {}) == {}, \"offset of {0}.{1}\");\n",
+                    stringify!($ty),
+                    stringify!($field),
+                    offset_of!($ty, $field)
+                );)*
+            };
+        }
+
+        layout!(DiagnosticSeverity {});
+        layout!(ErrorKind {});
+        layout!(SourceLocation {
+            line,
+            column,
+            length
+        });
+        layout!(Diagnostic {
+            severity,
+            message,
+            message_len,
+            location
+        });
+        layout!(Buffer { data, len });
+        layout!(BufferArray { buffers, len });
+        layout!(CompileResult {
+            success,
+            diagnostics,
+            diagnostics_len,
+            document,
+            error_kind
+        });
+        layout!(CompileResultArray { results, len });
+        layout!(CompilerOptions {
+            struct_size,
+            include_system_fonts,
+            inputs_json,
+            inputs_json_len,
+            custom_font_paths,
+            custom_font_paths_len,
+            package_path,
+            package_path_len,
+            allowed_extensions,
+            allowed_extensions_len,
+            max_file_size,
+            max_total_read_bytes,
+            forbid_filesystem,
+            max_compile_time_ms,
+            max_pages,
+            max_output_bytes,
+            use_fixed_date,
+            fixed_date_unix_seconds,
+            render_threads,
+            include_embedded_fonts,
+            package_cache_path,
+            package_cache_path_len,
+            package_registry_url,
+            package_registry_url_len,
+            offline_packages,
+            package_paths,
+            package_paths_len,
+            include_user_packages
+        });
+
+        let dir = std::env::temp_dir().join("typst_header_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let check_file = dir.join("layout.c");
+        std::fs::write(&check_file, checks).unwrap();
+
+        let include = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("include");
+        let output = Command::new(std::env::var("CC").unwrap_or("cc".into()))
+            .args(["-std=c11", "-fsyntax-only", "-Werror", "-I"])
+            .arg(&include)
+            .arg(&check_file)
+            .output();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let Ok(output) = output else {
+            // CI sets this, so a missing compiler can't pass silently there
+            assert!(
+                std::env::var_os("TYPST_NET_REQUIRE_C_COMPILER").is_none(),
+                "no C compiler found to check include/typst_net.h, but \
+                 TYPST_NET_REQUIRE_C_COMPILER is set; set CC"
+            );
+            eprintln!("no C compiler found (set CC), skipping header layout check");
+            return;
+        };
+        assert!(
+            output.status.success(),
+            "include/typst_net.h doesn't match the Rust layout:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// The committed header must be the one build.rs generates from the sources
+    #[test]
+    fn test_c_header_up_to_date() {
+        let generated_path = concat!(env!("OUT_DIR"), "/typst_net.h");
+        let committed_path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/typst_net.h");
+
+        let generated = std::fs::read_to_string(generated_path)
+            .expect("build.rs failed to generate typst_net.h, see the build warnings");
+        let committed = std::fs::read_to_string(committed_path).unwrap();
+
+        assert!(
+            generated == committed,
+            "include/typst_net.h is out of date, update it with:\n  cp {} {}",
+            generated_path,
+            committed_path
+        );
+    }
+
     #[test]
     fn test_cache_reset() {
         // Should not panic
@@ -1,4 +1,6 @@
-use crate::types::{Buffer, BufferArray, Diagnostic, SourceLocation};
+use crate::types::{
+    Buffer, BufferArray, CompileResult, CompileResultArray, Diagnostic, SourceLocation,
+};
 use std::ptr;
 
 /// Converts a String to a raw UTF-8 buffer owned by caller
@@ -49,6 +51,32 @@ pub fn vecs_to_buffer_array(vecs: Vec<Vec<u8>>) -> BufferArray {
     array
 }
 
+/// Converts Vec<CompileResult> to a CompileResultArray owned by caller
+///
+/// The pointer is never null, even without results, so an empty array can
+/// be told apart from a failure. Caller must free with `typst_net_result_array_free`
+pub fn results_to_array(results: Vec<CompileResult>) -> CompileResultArray {
+    // A boxed slice's allocation is exactly `len` long, unlike a Vec's
+    let len = results.len();
+    CompileResultArray {
+        results: Box::into_raw(results.into_boxed_slice()) as *mut CompileResult,
+        len,
+    }
+}
+
+/// Takes back ownership of the results in an array from `results_to_array`
+///
+/// # Safety
+/// - Array must have been created by `results_to_array`
+/// - Must only be called once per array
+pub unsafe fn take_results(array: CompileResultArray) -> Box<[CompileResult]> {
+    if array.results.is_null() {
+        return Box::default();
+    }
+
+    unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(array.results, array.len)) }
+}
+
 /// Creates a Diagnostic from components
 pub fn create_diagnostic(
     severity: crate::types::DiagnosticSeverity,
@@ -191,24 +219,24 @@ mod tests {
             "error 1".to_string(),
             Some((10, 5, 3)),
         );
-        
+
         let diag2 = create_diagnostic(
             crate::types::DiagnosticSeverity::Warning,
             "warning 1".to_string(),
             Some((20, 10, 5)),
         );
-        
+
         let diagnostics = vec![diag1, diag2];
         let (ptr, len) = diagnostics_to_array(diagnostics);
-        
+
         assert!(!ptr.is_null());
         assert_eq!(len, 2);
-        
+
         unsafe {
             free_diagnostics(ptr, len);
         }
     }
-    
+
     #[test]
     fn test_create_diagnostic_values() {
         let diag = create_diagnostic(
@@ -216,13 +244,13 @@ mod tests {
             "test error".to_string(),
             Some((10, 5, 3)),
         );
-        
+
         assert_eq!(diag.location.line, 10);
         assert_eq!(diag.location.column, 5);
         assert_eq!(diag.location.length, 3);
         assert!(!diag.message.is_null());
         assert_eq!(diag.message_len, "test error".len());
-        
+
         unsafe {
             let _ = Vec::from_raw_parts(diag.message, diag.message_len, diag.message_len);
         }
@@ -1,3 +1,5 @@
+use std::mem::offset_of;
+
 /// Severity level for diagnostics
 #[repr(u8)]
 pub enum DiagnosticSeverity {
@@ -6,8 +8,40 @@ pub enum DiagnosticSeverity {
     Hint = 2,
 }
 
+/// Why an operation failed
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The operation succeeded
+    None = 0,
+    /// Compilation errors (see diagnostics) or a rendering failure
+    Failed = 1,
+    /// Compilation took longer than `max_compile_time_ms`
+    TimeLimitExceeded = 2,
+    /// The document has more pages than `max_pages`
+    PageLimitExceeded = 3,
+    /// Rendered output is larger than `max_output_bytes`
+    OutputLimitExceeded = 4,
+    /// The operation's cancellation token was triggered
+    Cancelled = 5,
+}
+
+impl ErrorKind {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::None,
+            2 => Self::TimeLimitExceeded,
+            3 => Self::PageLimitExceeded,
+            4 => Self::OutputLimitExceeded,
+            5 => Self::Cancelled,
+            _ => Self::Failed,
+        }
+    }
+}
+
 /// Source code location information
 #[repr(C)]
+#[derive(Default)]
 pub struct SourceLocation {
     /// 1-indexed line number (0 if unavailable)
     pub line: u32,
@@ -52,11 +86,23 @@ pub struct CompileResult {
     pub diagnostics_len: usize,
     /// Opaque document handle (null if compilation failed)
     pub document: *mut std::ffi::c_void,
+    /// Reason for failure (`None` on success)
+    pub error_kind: ErrorKind,
 }
 
+/// Array of compile results (batch compilation)
 #[repr(C)]
-#[derive(Copy, Clone, Default)]
+pub struct CompileResultArray {
+    pub results: *mut CompileResult,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
 pub struct CompilerOptions {
+    /// Size of this struct as the caller compiled it (`sizeof(CompilerOptions)`).
+    /// Fields past it were added after the caller was built and keep their defaults
+    pub struct_size: usize,
     /// Include system fonts (default: true)
     pub include_system_fonts: bool,
     /// JSON string of inputs {"key" : "content"}
@@ -65,31 +111,140 @@ pub struct CompilerOptions {
     /// Custom font paths (array of UTF-8 strings)
     pub custom_font_paths: *const u8,
     pub custom_font_paths_len: usize,
-    /// Package path for offline packages
+    /// Package path for offline packages (unpacked directories or `.tar.gz` archives)
     pub package_path: *const u8,
     pub package_path_len: usize,
-    // future additions: e.g. PDF output options down here
+    /// Sandbox: allowed file extensions (JSON array of strings, e.g. ["typ", "png"]).
+    /// Null or empty allows every extension
+    pub allowed_extensions: *const u8,
+    pub allowed_extensions_len: usize,
+    /// Sandbox: maximum size of a single file read from disk (0 = unlimited)
+    pub max_file_size: u64,
+    /// Sandbox: maximum bytes read from disk per compilation (0 = unlimited)
+    pub max_total_read_bytes: u64,
+    /// Sandbox: deny all filesystem access, only the main source is available
+    pub forbid_filesystem: bool,
+    /// Limit: maximum wall-clock time per compilation in milliseconds (0 = unlimited).
+    /// The compile call returns at the limit, but the CPU is not reclaimed: typst
+    /// keeps running on a pool thread until it finishes (see `typst_net_abandoned_compiles`)
+    pub max_compile_time_ms: u64,
+    /// Limit: maximum number of pages per document (0 = unlimited), checked after layout
+    pub max_pages: u64,
+    /// Limit: maximum size of a single render call's output in bytes (0 = unlimited)
+    pub max_output_bytes: u64,
+    /// Use `fixed_date_unix_seconds` as the current date instead of the clock
+    /// (otherwise `SOURCE_DATE_EPOCH` is honored when set)
+    pub use_fixed_date: bool,
+    /// Fixed date as Unix timestamp in seconds, used by `datetime.today()` and PDF metadata
+    pub fixed_date_unix_seconds: i64,
+    /// Threads used to render pages in parallel (0 = one per CPU core)
+    pub render_threads: u32,
+    /// Include the fonts bundled with the library (needs the `embed-fonts` feature)
+    pub include_embedded_fonts: bool,
+    /// Cache directory for downloaded packages. Null or empty disables downloading
+    pub package_cache_path: *const u8,
+    pub package_cache_path_len: usize,
+    /// Registry base URL packages are downloaded from (null = https://packages.typst.org)
+    pub package_registry_url: *const u8,
+    pub package_registry_url_len: usize,
+    /// Only use packages already in the cache, never download
+    pub offline_packages: bool,
+    /// More package roots searched after `package_path`, in order (JSON array of strings)
+    pub package_paths: *const u8,
+    pub package_paths_len: usize,
+    /// Search the user package directory (where the typst CLI keeps `@local`) last
+    pub include_user_packages: bool,
+    // future additions: e.g. PDF output options down here.
+    // Only append, and start each addition at or past the previous
+    // `size_of::<CompilerOptions>()` (pad if needed): an older caller's
+    // trailing padding is covered by its `struct_size` and must not be read
+    // as a new field.
     // pub pdf_standard: u8,
     // pub pdf_tagged: bool, etc...
 }
 
-impl Default for CompileResult {
+impl Default for CompilerOptions {
+    /// Options used when none are passed: bundled fonts only, no limits
     fn default() -> Self {
         Self {
-            success: false,
-            diagnostics: std::ptr::null_mut(),
-            diagnostics_len: 0,
-            document: std::ptr::null_mut(),
+            struct_size: size_of::<Self>(),
+            include_system_fonts: false,
+            inputs_json: std::ptr::null(),
+            inputs_json_len: 0,
+            custom_font_paths: std::ptr::null(),
+            custom_font_paths_len: 0,
+            package_path: std::ptr::null(),
+            package_path_len: 0,
+            allowed_extensions: std::ptr::null(),
+            allowed_extensions_len: 0,
+            max_file_size: 0,
+            max_total_read_bytes: 0,
+            forbid_filesystem: false,
+            max_compile_time_ms: 0,
+            max_pages: 0,
+            max_output_bytes: 0,
+            use_fixed_date: false,
+            fixed_date_unix_seconds: 0,
+            render_threads: 0,
+            include_embedded_fonts: true,
+            package_cache_path: std::ptr::null(),
+            package_cache_path_len: 0,
+            package_registry_url: std::ptr::null(),
+            package_registry_url_len: 0,
+            offline_packages: false,
+            package_paths: std::ptr::null(),
+            package_paths_len: 0,
+            include_user_packages: false,
         }
     }
 }
 
-impl Default for SourceLocation {
+impl CompilerOptions {
+    /// Smallest `struct_size` accepted: every field up to `include_user_packages`,
+    /// the last one present when `struct_size` was introduced
+    pub const MIN_SIZE: usize =
+        offset_of!(CompilerOptions, include_user_packages) + size_of::<bool>();
+
+    /// Read options passed over FFI, taking only the fields the caller's
+    /// `struct_size` covers
+    ///
+    /// # Returns
+    /// Defaults for a null pointer, `None` if `struct_size` is too small
+    ///
+    /// # Safety
+    /// `options` must be null or point to `struct_size` readable bytes
+    pub unsafe fn read(options: *const CompilerOptions) -> Option<CompilerOptions> {
+        let mut opts = CompilerOptions::default();
+        if options.is_null() {
+            return Some(opts);
+        }
+
+        let struct_size = unsafe { (*options).struct_size };
+        if struct_size < Self::MIN_SIZE {
+            return None;
+        }
+
+        let len = struct_size.min(size_of::<Self>());
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                options as *const u8,
+                &mut opts as *mut CompilerOptions as *mut u8,
+                len,
+            );
+        }
+        opts.struct_size = size_of::<Self>();
+        Some(opts)
+    }
+}
+
+impl Default for CompileResult {
     fn default() -> Self {
         Self {
-            line: 0,
-            column: 0,
-            length: 0,
+            success: false,
+            diagnostics: std::ptr::null_mut(),
+            diagnostics_len: 0,
+            document: std::ptr::null_mut(),
+            error_kind: ErrorKind::Failed,
         }
     }
 }
@@ -1,25 +1,71 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use time::{OffsetDateTime, UtcOffset};
 // ============================================================================
 // TYPST IMPORTS - ONLY IN THIS FILE
 // ============================================================================
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use resvg::{tiny_skia, usvg};
 use serde_json::Value as JsonValue;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 /// ISOLATION LAYER: This is the ONLY file that import typst types.
 /// All typst API interaction happens here. When typst releases a new version,
 /// only this file should need to be updated.
-use typst::diag::{FileError, FileResult, SourceDiagnostic};
+use typst::diag::{FileError, FileResult, PackageError, SourceDiagnostic, SourceResult, Warned};
+use typst::ecow::{EcoString, eco_format};
 use typst::foundations::{Bytes, Datetime, Dict, Value};
-use typst::layout::{Page, PagedDocument};
-use typst::syntax::{FileId, Source, VirtualPath};
-use typst::text::{Font, FontBook};
+use typst::layout::{Frame, FrameItem, Page, PagedDocument};
+use typst::syntax::package::{PackageManifest, PackageSpec};
+use typst::syntax::{FileId, Source, Span, SyntaxError, VirtualPath};
+use typst::text::{Coverage, Font, FontBook, FontStyle, TextItem};
 use typst::utils::LazyHash;
 use typst::{Library, LibraryExt, World};
 use typst_kit::fonts::{FontSearcher, Fonts};
-use typst_pdf::{PdfOptions, pdf};
+use typst_pdf::{PdfOptions, Timestamp, pdf};
 use typst_svg::svg;
 
-// TODO: Add PNG export support
+use crate::cancel::CancellationToken;
+use crate::compile_pool;
+
+/// Registry packages are downloaded from when no URL is configured
+pub const DEFAULT_PACKAGE_REGISTRY: &str = typst_kit::package::DEFAULT_REGISTRY;
+
+/// Manifest file at the root of every package
+const MANIFEST_FILE: &str = "typst.toml";
+
+/// Namespace of packages that only exist locally and are never downloaded
+const LOCAL_NAMESPACE: &str = "local";
+
+/// User agent sent when downloading packages
+const USER_AGENT: &str = concat!("typst-net/", env!("CARGO_PKG_VERSION"));
+
+/// Most bytes a package archive may decompress to, whatever the sandbox allows
+const MAX_ARCHIVE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Most bytes a single file of a package archive may decompress to
+const MAX_ARCHIVE_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Most decompressed bytes of package archives kept in memory by a world and its forks
+const MAX_CACHED_ARCHIVE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Most bytes downloaded for a single package archive
+const MAX_DOWNLOAD_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How long connecting to the package registry may take
+const DOWNLOAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a package download may go without receiving data
+const DOWNLOAD_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a compilation with a time limit or cancellation token is checked
+const INTERRUPT_POLL: Duration = Duration::from_millis(10);
+
 // TODO: Add HTML export support (typst::compile::<HtmlDocument>)
 
 // ============================================================================
@@ -32,17 +78,527 @@ pub struct BackendWorld {
     root: PathBuf,
     main_source: Source,
     main_id: FileId,
-    fonts: Fonts,
-    font_book: LazyHash<FontBook>,
-    library: LazyHash<Library>,
+    /// Shared with forked worlds and other compilers using the same store
+    font_store: FontStore,
+    library: Arc<LazyHash<Library>>,
     source_cache: HashMap<FileId, Source>,
+    #[allow(dead_code)]
     binary_cache: HashMap<FileId, Bytes>, // unimplemented for now
-    package_path: Option<PathBuf>,
+    /// Package roots in lookup order (canonical)
+    package_paths: Vec<PathBuf>,
+    sandbox: SandboxPolicy,
+    /// Bytes read from disk during the current compilation
+    bytes_read: AtomicU64,
+    /// Packages read from during the current compilation, in order of first use
+    used_packages: Mutex<Vec<BackendPackageUsage>>,
+    /// Files read from disk during the current compilation, in order of first read
+    dependencies: Mutex<Vec<PathBuf>>,
+    limits: ResourceLimits,
+    /// Point in time after which file access fails (current compilation only)
+    deadline: Option<Instant>,
+    /// Cancellation token observed by the current compilation
+    cancel_token: Option<Arc<CancellationToken>>,
+    /// Fixed "now" used by `datetime.today()` and PDF metadata instead of the clock
+    fixed_date: Option<OffsetDateTime>,
+    /// Dedicated pool for rendering pages (global rayon pool if `None`)
+    render_pool: Option<Arc<ThreadPool>>,
+    /// Registry for packages that are not in the package path
+    package_registry: Option<PackageRegistry>,
+    /// Package archives decompressed so far, shared with forked worlds
+    package_archives: Arc<Mutex<ArchiveCache>>,
+    /// Archives checked against their file (current compilation only)
+    checked_archives: Mutex<HashMap<PackageSpec, Arc<PackageArchive>>>,
+    /// Packages whose manifest passed validation (current compilation only)
+    validated_packages: Mutex<HashSet<PackageSpec>>,
+}
+
+/// Archive of one package, locked on its own while it is opened or downloaded
+type ArchiveSlot = Arc<Mutex<Option<Arc<PackageArchive>>>>;
+
+/// Decompressed package archives, least recently used dropped first once
+/// they take more than `max_bytes`
+#[derive(Debug)]
+struct ArchiveCache {
+    entries: HashMap<PackageSpec, CachedArchive>,
+    /// Decompressed bytes of the cached archives
+    bytes: u64,
+    max_bytes: u64,
+    /// Incremented on every access, orders entries by last use
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct CachedArchive {
+    slot: ArchiveSlot,
+    /// Decompressed size of the archive in `slot` (0 while it is loading)
+    bytes: u64,
+    last_used: u64,
+}
+
+/// Modification time and length of an archive file, to notice when it is replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ArchiveStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Where a package root stores a package
+enum PackageLocation {
+    /// Unpacked `namespace/name/version` directory (canonical)
+    Directory(PathBuf),
+    /// `namespace/name-version.tar.gz` archive (canonical)
+    Archive(PathBuf),
+}
+
+/// Files of a `name-version.tar.gz` package archive, decompressed in memory
+#[derive(Debug)]
+struct PackageArchive {
+    /// Location of the archive on disk
+    path: PathBuf,
+    /// State of the file at `path` when it was read (`None` if unknown)
+    stamp: Option<ArchiveStamp>,
+    /// File contents by path relative to the package root
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+/// Searched fonts that can be shared by many worlds
+///
+/// Font files are loaded lazily and only once, whichever world uses them first.
+#[derive(Debug, Clone)]
+pub struct FontStore {
+    fonts: Arc<Fonts>,
+    /// Fonts added from memory, indexed after `fonts`
+    extra_fonts: Arc<Vec<Font>>,
+    book: Arc<LazyHash<FontBook>>,
+    /// Directories searched for custom fonts (to tell them apart from system fonts)
+    custom_font_paths: Arc<Vec<PathBuf>>,
+}
+
+/// Where a loaded font comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontSource {
+    /// Bundled with the library
+    Embedded,
+    /// Found in a system font directory
+    System(PathBuf),
+    /// Found in one of the `custom_font_paths`
+    Custom(PathBuf),
+    /// Added from memory with `add_font`
+    Memory,
+}
+
+/// Description of one loaded font face
+#[derive(Debug, Clone)]
+pub struct BackendFontEntry {
+    pub family: String,
+    /// "normal", "italic" or "oblique"
+    pub style: &'static str,
+    /// 100 (thin) to 900 (black), 400 = regular
+    pub weight: u16,
+    /// Width relative to normal (1.0), from 0.5 to 2.0
+    pub stretch: f64,
+    pub source: FontSource,
+    /// Index of the face in its font collection (0 for single fonts)
+    pub collection_index: u32,
+    /// Covered codepoints as inclusive ranges
+    pub coverage: Vec<(u32, u32)>,
+}
+
+/// Restrictions on which files a compilation may read from disk.
+///
+/// The main source is never affected. Zero limits and an empty extension
+/// list mean "unrestricted".
+#[derive(Debug, Clone, Default)]
+pub struct SandboxPolicy {
+    /// Lowercase file extensions (without dot) that may be read
+    pub allowed_extensions: Vec<String>,
+    /// Maximum size of a single file in bytes
+    pub max_file_size: u64,
+    /// Maximum bytes read from disk over one compilation
+    pub max_total_bytes: u64,
+    /// Deny every filesystem read (workspace and packages)
+    pub forbid_filesystem: bool,
+}
+
+/// Caps on the resources a single compilation and its outputs may use.
+///
+/// Zero means "unlimited".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceLimits {
+    /// Maximum wall-clock time of one compilation in milliseconds
+    ///
+    /// The compile call returns once it is reached, but typst can't be stopped
+    /// mid-evaluation: the abandoned compilation keeps its compile pool worker
+    /// and CPU core busy until it finishes (its file reads fail from then on).
+    /// The CPU time is not reclaimed; see `compile_pool::abandoned_jobs`.
+    pub max_compile_time_ms: u64,
+    /// Maximum number of pages in the compiled document
+    ///
+    /// Checked once layout finished, so the layout work itself is only
+    /// bounded by `max_compile_time_ms`.
+    pub max_pages: u64,
+    /// Maximum size of one render call's output in bytes
+    pub max_output_bytes: u64,
+}
+
+/// Where packages missing from the package path are downloaded from.
+#[derive(Debug, Clone)]
+pub struct PackageRegistry {
+    /// Registry base URL, archives are fetched from `{url}/{namespace}/{name}-{version}.tar.gz`
+    pub url: String,
+    /// Directory downloaded archives are stored in, laid out like the package path
+    pub cache_path: PathBuf,
+    /// Only use archives already in the cache, never download
+    pub offline: bool,
+}
+
+/// Why a compilation was stopped before producing a result
+///
+/// Output size is reported by `RenderError` instead, as it is only known
+/// once a document is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortReason {
+    TimeLimit,
+    PageLimit,
+    Cancelled,
+}
+
+impl ArchiveCache {
+    fn new(max_bytes: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            bytes: 0,
+            max_bytes,
+            clock: 0,
+        }
+    }
+
+    /// Slot of a package's archive, marked as just used
+    fn slot(&mut self, spec: &PackageSpec) -> ArchiveSlot {
+        self.clock += 1;
+        let entry = self
+            .entries
+            .entry(spec.clone())
+            .or_insert_with(|| CachedArchive {
+                slot: Arc::default(),
+                bytes: 0,
+                last_used: 0,
+            });
+        entry.last_used = self.clock;
+        Arc::clone(&entry.slot)
+    }
+
+    /// Account for an archive of `bytes` just stored in `slot`
+    ///
+    /// Evicts the least recently used other archives while the cache is over
+    /// its limit. Worlds still using an evicted archive keep their reference.
+    fn loaded(&mut self, spec: &PackageSpec, slot: &ArchiveSlot, bytes: u64) {
+        // Evicted while it was loading, the archive is not cached
+        let Some(entry) = self
+            .entries
+            .get_mut(spec)
+            .filter(|entry| Arc::ptr_eq(&entry.slot, slot))
+        else {
+            return;
+        };
+        self.bytes = self.bytes - entry.bytes + bytes;
+        entry.bytes = bytes;
+
+        while self.bytes > self.max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .filter(|(cached, _)| *cached != spec)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(cached, _)| cached.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.bytes;
+            }
+        }
+    }
+}
+
+impl ArchiveStamp {
+    fn of(metadata: &fs::Metadata) -> Option<Self> {
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+impl PackageArchive {
+    /// Decompress a `.tar.gz` package, reading at most `max_bytes` (0 = no sandbox limit)
+    ///
+    /// Expansion is always capped by `MAX_ARCHIVE_BYTES` and, per file, by
+    /// `MAX_ARCHIVE_ENTRY_BYTES`, so a small archive can't exhaust memory.
+    /// Entries that are not regular files or that point outside the package
+    /// (absolute paths, `..`) are skipped.
+    fn open(path: &Path, max_bytes: u64) -> Result<Self, String> {
+        let file = fs::File::open(path)
+            .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        let stamp = file.metadata().ok().as_ref().and_then(ArchiveStamp::of);
+        let mut archive = Self::read(file, &path.display().to_string(), path, max_bytes)?;
+        archive.stamp = stamp;
+        Ok(archive)
+    }
+
+    /// Whether the archive still matches the file at `path`
+    fn is_current(&self, path: &Path) -> bool {
+        self.path == path
+            && self.stamp.is_some()
+            && self.stamp == fs::metadata(path).ok().as_ref().and_then(ArchiveStamp::of)
+    }
+
+    /// Decompressed size of all files
+    fn size(&self) -> u64 {
+        self.files.values().map(|data| data.len() as u64).sum()
+    }
+
+    /// Decompress a `.tar.gz` package stored at `path` from `reader`, `name` is used in errors
+    fn read(reader: impl Read, name: &str, path: &Path, max_bytes: u64) -> Result<Self, String> {
+        let mut archive = tar::Archive::new(GzDecoder::new(reader));
+        let max_bytes = match max_bytes {
+            0 => MAX_ARCHIVE_BYTES,
+            max => max.min(MAX_ARCHIVE_BYTES),
+        };
+
+        let mut files = HashMap::new();
+        let mut total: u64 = 0;
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("failed to read {}: {}", name, e))?;
+
+        for entry in entries {
+            let mut entry = entry.map_err(|e| format!("failed to read {}: {}", name, e))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let Some(relative) = entry.path().ok().and_then(|p| package_relative_path(&p)) else {
+                continue;
+            };
+
+            if entry.size() > MAX_ARCHIVE_ENTRY_BYTES {
+                return Err(format!(
+                    "{}: {} is larger than {} bytes",
+                    name,
+                    relative.display(),
+                    MAX_ARCHIVE_ENTRY_BYTES
+                ));
+            }
+
+            total += entry.size();
+            if total > max_bytes {
+                return Err(format!(
+                    "{} decompresses to more than {} bytes",
+                    name, max_bytes
+                ));
+            }
+
+            // Bounded as well, in case the entry yields more than its header claims
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            (&mut entry)
+                .take(MAX_ARCHIVE_ENTRY_BYTES + 1)
+                .read_to_end(&mut data)
+                .map_err(|e| format!("failed to read {}: {}", name, e))?;
+            if data.len() as u64 > entry.size() {
+                return Err(format!("{}: {} is malformed", name, relative.display()));
+            }
+            files.insert(relative, data);
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            stamp: None,
+            files,
+        })
+    }
+}
+
+impl PackageRegistry {
+    /// URL of a package archive: `{url}/{namespace}/{name}-{version}.tar.gz`
+    fn archive_url(&self, spec: &PackageSpec) -> String {
+        format!(
+            "{}/{}/{}-{}.tar.gz",
+            self.url.trim_end_matches('/'),
+            spec.namespace,
+            spec.name,
+            spec.version
+        )
+    }
+
+    /// Download a package and store its archive in the cache
+    ///
+    /// The archive is only cached once it decompresses successfully.
+    /// `check_interrupted` is called between chunks of the body, so a
+    /// download stops with the compilation that needs it.
+    fn download(
+        &self,
+        spec: &PackageSpec,
+        max_bytes: u64,
+        check_interrupted: impl Fn() -> FileResult<()>,
+    ) -> FileResult<PackageArchive> {
+        if self.offline {
+            return Err(FileError::Package(PackageError::NotFound(spec.clone())));
+        }
+
+        let url = self.archive_url(spec);
+        let network_failed = |e: &dyn std::fmt::Display| {
+            FileError::Package(PackageError::NetworkFailed(Some(
+                format!("failed to download {}: {}", url, e).into(),
+            )))
+        };
+
+        let response = match download_agent(&url)
+            .map_err(|e| network_failed(&e))?
+            .get(&url)
+            .call()
+        {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => {
+                return Err(FileError::Package(PackageError::NotFound(spec.clone())));
+            }
+            Err(e) => return Err(network_failed(&e)),
+        };
+
+        let mut reader = response.into_reader().take(MAX_DOWNLOAD_BYTES + 1);
+        let mut data = Vec::new();
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            check_interrupted()?;
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => data.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(network_failed(&e)),
+            }
+        }
+        if data.len() as u64 > MAX_DOWNLOAD_BYTES {
+            return Err(network_failed(&format_args!(
+                "archive is larger than {} bytes",
+                MAX_DOWNLOAD_BYTES
+            )));
+        }
+
+        let namespace_dir = self.cache_path.join(spec.namespace.as_str());
+        let path = namespace_dir.join(format!("{}-{}.tar.gz", spec.name, spec.version));
+        let mut archive = PackageArchive::read(data.as_slice(), &url, &path, max_bytes)
+            .map_err(|e| FileError::Package(PackageError::MalformedArchive(Some(e.into()))))?;
+
+        // Write to a temporary file first, so a concurrent reader never sees half an archive
+        let partial = path.with_extension(format!("gz.{}.part", std::process::id()));
+        fs::create_dir_all(&namespace_dir)
+            .and_then(|_| fs::write(&partial, &data))
+            .and_then(|_| fs::rename(&partial, &path))
+            .map_err(|e| FileError::from_io(e, &path))?;
+        archive.stamp = fs::metadata(&path).ok().as_ref().and_then(ArchiveStamp::of);
+
+        Ok(archive)
+    }
+}
+
+/// HTTP agent for package downloads, honoring the proxy environment variables
+/// like typst's own downloader but with timeouts
+fn download_agent(url: &str) -> Result<ureq::Agent, ureq::native_tls::Error> {
+    let mut builder = ureq::AgentBuilder::new()
+        .user_agent(USER_AGENT)
+        .timeout_connect(DOWNLOAD_CONNECT_TIMEOUT)
+        .timeout_read(DOWNLOAD_READ_TIMEOUT)
+        .tls_connector(Arc::new(ureq::native_tls::TlsConnector::new()?));
+
+    if let Some(proxy) = env_proxy::for_url_str(url)
+        .to_url()
+        .and_then(|proxy| ureq::Proxy::new(proxy).ok())
+    {
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build())
 }
 
 /// Wrapper around typst's compiled document
 pub struct BackendDocument {
     inner: PagedDocument,
+    max_output_bytes: u64,
+    /// Creation timestamp written to PDF metadata (UTC)
+    timestamp: Option<Datetime>,
+    /// Pool that renders pages in parallel (global rayon pool if `None`)
+    render_pool: Option<Arc<ThreadPool>>,
+    /// Packages the document was compiled from
+    packages: Vec<BackendPackageUsage>,
+}
+
+/// Fonts used by a compiled document and characters no font could display
+#[derive(Debug, Clone, Default)]
+pub struct BackendFontReport {
+    /// Fonts in order of first use
+    pub fonts: Vec<BackendFontUsage>,
+    /// Characters rendered as the missing-glyph box ("tofu"), in order of first use
+    pub missing_glyphs: Vec<BackendMissingGlyph>,
+}
+
+/// One font face used by a document
+#[derive(Debug, Clone)]
+pub struct BackendFontUsage {
+    pub family: String,
+    pub style: &'static str,
+    pub weight: u16,
+    /// Zero-based indices of the pages using the font
+    pub pages: Vec<usize>,
+}
+
+/// Text that was rendered without a glyph
+#[derive(Debug, Clone)]
+pub struct BackendMissingGlyph {
+    /// The character (or cluster) that has no glyph
+    pub text: String,
+    /// Family of the font the missing glyph was taken from
+    pub font_family: String,
+    /// Zero-based indices of the pages it appears on
+    pub pages: Vec<usize>,
+}
+
+/// A package read from during a compilation
+#[derive(Debug, Clone)]
+pub struct BackendPackageUsage {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+    /// Canonical path of the package directory or `.tar.gz` archive
+    pub path: PathBuf,
+    /// Whether `path` is an archive
+    pub archive: bool,
+}
+
+/// Error produced while rendering a document
+#[derive(Debug)]
+pub enum RenderError {
+    /// The rendered output is larger than `ResourceLimits::max_output_bytes`
+    OutputLimitExceeded {
+        size: u64,
+        limit: u64,
+    },
+    /// The cancellation token was triggered
+    Cancelled,
+    Failed(String),
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutputLimitExceeded { size, limit } => write!(
+                f,
+                "Rendered output is {} bytes, exceeding the limit of {} bytes",
+                size, limit
+            ),
+            Self::Cancelled => f.write_str("Rendering was cancelled"),
+            Self::Failed(message) => f.write_str(message),
+        }
+    }
 }
 
 /// Internal diagnostic representation
@@ -51,6 +607,9 @@ pub struct BackendDiagnostic {
     pub severity: DiagnosticSeverity,
     pub message: String,
     pub location: Option<BackendLocation>,
+    /// File `location` points into: its path under the root, or
+    /// `@namespace/name:version/path` for package files
+    pub file: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -71,18 +630,141 @@ pub struct BackendCompileResult {
     pub success: bool,
     pub document: Option<BackendDocument>,
     pub diagnostics: Vec<BackendDiagnostic>,
+    /// Set when compilation was stopped by a resource limit or cancellation
+    pub aborted: Option<AbortReason>,
 }
 
 // ============================================================================
 // WORLD IMPLEMENTATION
 // ============================================================================
+impl FontStore {
+    /// Search custom font directories, system fonts and embedded fonts (in that priority)
+    ///
+    /// Embedded fonts are only available with the `embed-fonts` feature.
+    pub fn search(
+        custom_font_paths: Vec<PathBuf>,
+        include_system_fonts: bool,
+        include_embedded_fonts: bool,
+    ) -> Self {
+        let mut searcher = FontSearcher::new();
+        searcher.include_system_fonts(include_system_fonts);
+        #[cfg(feature = "embed-fonts")]
+        searcher.include_embedded_fonts(include_embedded_fonts);
+        #[cfg(not(feature = "embed-fonts"))]
+        let _ = include_embedded_fonts;
+
+        let fonts = searcher.search_with(&custom_font_paths);
+        Self {
+            book: Arc::new(LazyHash::new(fonts.book.clone())),
+            fonts: Arc::new(fonts),
+            extra_fonts: Arc::new(Vec::new()),
+            custom_font_paths: Arc::new(custom_font_paths),
+        }
+    }
+
+    /// Describe every font face in the book, in lookup order
+    pub fn entries(&self) -> Vec<BackendFontEntry> {
+        let searched = self.fonts.fonts.len();
+
+        (0..)
+            .map_while(|index| self.book.info(index).map(|info| (index, info)))
+            .map(|(index, info)| {
+                let (source, collection_index) = match self.fonts.fonts.get(index) {
+                    Some(slot) => (self.file_source(slot.path()), slot.index()),
+                    None => (
+                        FontSource::Memory,
+                        self.extra_fonts[index - searched].index(),
+                    ),
+                };
+
+                BackendFontEntry {
+                    family: info.family.clone(),
+                    style: style_name(info.variant.style),
+                    weight: info.variant.weight.to_number(),
+                    stretch: info.variant.stretch.to_ratio().get(),
+                    source,
+                    collection_index,
+                    coverage: coverage_ranges(&info.coverage),
+                }
+            })
+            .collect()
+    }
+
+    /// Classify a searched font by its path (`None` = embedded)
+    fn file_source(&self, path: Option<&Path>) -> FontSource {
+        match path {
+            None => FontSource::Embedded,
+            Some(path)
+                if self
+                    .custom_font_paths
+                    .iter()
+                    .any(|dir| path.starts_with(dir)) =>
+            {
+                FontSource::Custom(path.to_path_buf())
+            }
+            Some(path) => FontSource::System(path.to_path_buf()),
+        }
+    }
+
+    /// Add every face in `data` (a font file or collection), returning how many were added
+    ///
+    /// Copy-on-write: other holders of this store keep their fonts unchanged.
+    pub fn add_font(&mut self, data: Vec<u8>) -> usize {
+        let faces: Vec<Font> = Font::iter(Bytes::new(data)).collect();
+        if faces.is_empty() {
+            return 0;
+        }
+
+        let book = Arc::make_mut(&mut self.book);
+        let extra_fonts = Arc::make_mut(&mut self.extra_fonts);
+        for font in &faces {
+            book.push(font.info().clone());
+            extra_fonts.push(font.clone());
+        }
+
+        faces.len()
+    }
+
+    /// Get the font at `index` in the book, loading it on first access
+    fn font(&self, index: usize) -> Option<Font> {
+        match self.fonts.fonts.get(index) {
+            Some(slot) => slot.get(),
+            None => self
+                .extra_fonts
+                .get(index - self.fonts.fonts.len())
+                .cloned(),
+        }
+    }
+}
+
 impl BackendWorld {
+    /// Create a world with its own font search (compilers go through `with_fonts`)
+    #[cfg(test)]
     pub fn new(
         root: PathBuf,
         inputs_json: Option<&str>,
         package_path: Option<PathBuf>,
         custom_font_paths: Vec<PathBuf>,
         include_system_fonts: bool,
+    ) -> Result<Self, String> {
+        let fonts = FontStore::search(custom_font_paths, include_system_fonts, true);
+        Self::with_fonts(
+            root,
+            inputs_json,
+            package_path.into_iter().collect(),
+            &fonts,
+        )
+    }
+
+    /// Create a world that shares an existing font store instead of searching fonts
+    ///
+    /// Packages are looked up in `package_paths` in order, so an earlier root
+    /// can override a package of a later one.
+    pub fn with_fonts(
+        root: PathBuf,
+        inputs_json: Option<&str>,
+        package_paths: Vec<PathBuf>,
+        fonts: &FontStore,
     ) -> Result<Self, String> {
         // Validate root
         if !root.exists() {
@@ -93,8 +775,8 @@ impl BackendWorld {
             return Err(format!("Root path is not a directory: {}", root.display()));
         }
 
-        // Validate package_path if provided
-        if let Some(ref pkg_path) = package_path {
+        // Validate package paths
+        for pkg_path in &package_paths {
             if !pkg_path.exists() {
                 return Err(format!(
                     "Package path does not exist: {}",
@@ -109,28 +791,26 @@ impl BackendWorld {
             }
         }
 
-        // Initialize fonts
-        let mut searcher = FontSearcher::new();
-        searcher.include_system_fonts(include_system_fonts);
-
-        let fonts = searcher.search_with(custom_font_paths);
-        let font_book = LazyHash::new(fonts.book.clone());
-
-        // Parse JSON to Dict
-        let inputs = if let Some(json) = inputs_json {
-            let inputs_val: JsonValue =
-                serde_json::from_str(json).map_err(|e| format!("Invalid inputs JSON: {}", e))?;
-
-            match json_to_typst(inputs_val) {
-                Value::Dict(d) => d,
-                _ => Dict::new(),
-            }
-        } else {
-            Dict::new()
-        };
+        // Canonicalize roots once, so resolve_path can compare against them
+        let root = root
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve root path {}: {}", root.display(), e))?;
+
+        let package_paths = package_paths
+            .into_iter()
+            .map(|pkg_path| {
+                pkg_path.canonicalize().map_err(|e| {
+                    format!(
+                        "Failed to resolve package path {}: {}",
+                        pkg_path.display(),
+                        e
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         // Get library w/ inputs
-        let library = LazyHash::new(Library::builder().with_inputs(inputs).build());
+        let library = Arc::new(build_library(inputs_json)?);
 
         // Create empty main source
         let main_id = FileId::new(None, VirtualPath::new("main.typ"));
@@ -144,12 +824,24 @@ impl BackendWorld {
             root,
             main_source,
             main_id,
-            fonts,
-            font_book,
+            font_store: fonts.clone(),
             library,
             source_cache,
             binary_cache,
-            package_path,
+            package_paths,
+            sandbox: SandboxPolicy::default(),
+            bytes_read: AtomicU64::new(0),
+            used_packages: Mutex::default(),
+            dependencies: Mutex::default(),
+            limits: ResourceLimits::default(),
+            deadline: None,
+            cancel_token: None,
+            fixed_date: None,
+            render_pool: None,
+            package_registry: None,
+            package_archives: Arc::new(Mutex::new(ArchiveCache::new(MAX_CACHED_ARCHIVE_BYTES))),
+            checked_archives: Mutex::default(),
+            validated_packages: Mutex::default(),
         })
     }
 
@@ -157,410 +849,2619 @@ impl BackendWorld {
         self.main_source = Source::new(self.main_id, source_text.to_string());
     }
 
-    pub fn resolve_path(&self, id: FileId) -> FileResult<PathBuf> {
-        match id.package() {
-            // The file is a part of a package (@preview, etc.)
-            Some(spec) => {
-                if let Some(ref pkg_root) = self.package_path {
-                    let path = pkg_root
-                        .join(&spec.namespace.to_string())
-                        .join(&spec.name.to_string())
-                        .join(&spec.version.to_string())
-                        .join(id.vpath().as_rootless_path());
-
-                    if !path.exists() {
-                        return Err(FileError::NotFound(path));
-                    }
-                    Ok(path)
-                } else {
-                    // No package path configured, but user tried to use a package
-                    Err(FileError::AccessDenied)
-                }
-            }
-            None => {
-                let vpath = id.vpath();
-                let path = self.root.join(vpath.as_rootless_path());
+    /// Compile the main source as the file at `path` instead of `/main.typ`
+    ///
+    /// Relative paths in the source then resolve against the file's directory,
+    /// and the file on disk is replaced by the in-memory source.
+    pub fn set_main_file(&mut self, path: &Path) -> Result<(), String> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+        let vpath = VirtualPath::within_root(&path, &self.root).ok_or_else(|| {
+            format!(
+                "{} is outside the root {}",
+                path.display(),
+                self.root.display()
+            )
+        })?;
 
-                // Security check
-                let absolute_root = std::path::absolute(&self.root).unwrap_or(self.root.clone());
-                let absolute_path = std::path::absolute(&path).unwrap_or(path.clone());
+        self.main_id = FileId::new(None, vpath);
+        self.main_source = Source::new(self.main_id, self.main_source.text().to_string());
+        Ok(())
+    }
 
-                if !absolute_path.starts_with(&absolute_root) {
-                    return Err(FileError::AccessDenied);
-                }
+    /// Describe every font available to compilations
+    pub fn list_fonts(&self) -> Vec<BackendFontEntry> {
+        self.font_store.entries()
+    }
 
-                if !path.exists() {
-                    return Err(FileError::NotFound(path));
-                }
-                Ok(path)
-            }
-        }
+    /// Add fonts from memory for subsequent compilations (see `FontStore::add_font`)
+    pub fn add_font(&mut self, data: Vec<u8>) -> usize {
+        self.font_store.add_font(data)
     }
 
-    pub fn compile(&mut self) -> BackendCompileResult {
-        let warned = typst::compile::<PagedDocument>(self);
+    /// Replace `sys.inputs` for subsequent compilations
+    ///
+    /// Only the standard library is rebuilt; fonts and caches are kept.
+    pub fn set_inputs(&mut self, inputs_json: Option<&str>) -> Result<(), String> {
+        self.library = Arc::new(build_library(inputs_json)?);
+        Ok(())
+    }
 
-        // Extract diagnostics (warnings always present)
-        let warnings = warned
-            .warnings
-            .iter()
-            .map(|diag| convert_diagnostic(diag, self))
-            .collect();
+    /// Compile the main source once per input set, in parallel
+    ///
+    /// `inputs_json_array` is a JSON array of input objects (`null` = no inputs).
+    /// Every entry compiles in its own world sharing fonts and settings with
+    /// this one, so only `sys.inputs` differs. Results keep the input order.
+    ///
+    /// With a time limit or `cancel_token`, entries run on the compile pool
+    /// and each one is abandoned like a single compilation (see `run_compile`);
+    /// once the token is triggered, entries not started yet are skipped.
+    pub fn compile_batch(
+        &self,
+        inputs_json_array: &str,
+        cancel_token: Option<Arc<CancellationToken>>,
+    ) -> Result<Vec<BackendCompileResult>, String> {
+        let entries: Vec<JsonValue> = serde_json::from_str(inputs_json_array)
+            .map_err(|e| format!("Invalid batch inputs JSON: {}", e))?;
+
+        let library = |inputs: JsonValue| match inputs {
+            JsonValue::Object(_) | JsonValue::Null => Ok(Arc::new(library_from_json(inputs))),
+            other => Err(format!("Batch inputs must be JSON objects, got: {}", other)),
+        };
 
-        match warned.output {
-            Ok(document) => BackendCompileResult {
-                success: true,
-                document: Some(BackendDocument { inner: document }),
-                diagnostics: warnings,
-            },
-            Err(errors) => {
-                let mut all_diagnostics: Vec<BackendDiagnostic> = errors
-                    .iter()
-                    .map(|diag| convert_diagnostic(diag, self))
-                    .collect();
-                all_diagnostics.extend(warnings);
+        if (self.limits.max_compile_time_ms == 0 && cancel_token.is_none())
+            || compile_pool::on_worker()
+        {
+            return Ok(entries
+                .into_par_iter()
+                .map(|inputs| match library(inputs) {
+                    Ok(library) => self.fork(library).compile(),
+                    Err(e) => failed_result(e),
+                })
+                .collect());
+        }
 
-                BackendCompileResult {
-                    success: false,
-                    document: None,
-                    diagnostics: all_diagnostics,
+        // Submit every entry before waiting for any, so they run in parallel
+        let jobs: Vec<_> = entries
+            .into_iter()
+            .map(|inputs| {
+                let mut world = self.fork(library(inputs)?);
+                let token = cancel_token.clone();
+                let started = Arc::new(OnceLock::new());
+                let job_started = Arc::clone(&started);
+                let job = compile_pool::submit(move || {
+                    job_started.get_or_init(Instant::now);
+                    world.compile_impl(token)
+                })?;
+                Ok((job, started))
+            })
+            .collect();
+
+        let cancelled = || {
+            cancel_token
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled())
+        };
+        Ok(jobs
+            .into_iter()
+            .map(|job: Result<_, String>| {
+                let (job, started) = match job {
+                    Ok(job) => job,
+                    Err(e) => return failed_result(e),
+                };
+                // Queued entries are not timed yet, only cancelled
+                let interrupted = || {
+                    cancelled()
+                        || started
+                            .get()
+                            .is_some_and(|&started| self.deadline_elapsed(started))
+                };
+                match job.wait(INTERRUPT_POLL, interrupted) {
+                    Some(result) => result,
+                    None if cancelled() => cancelled_result(Vec::new()),
+                    None => self.time_limit_result(Vec::new()),
                 }
-            }
-        }
+            })
+            .collect())
     }
-}
 
-impl World for BackendWorld {
-    fn library(&self) -> &LazyHash<Library> {
-        &self.library
+    /// Create an independent world for compiling `source_text`
+    ///
+    /// Fonts, library and settings are shared with this world, so a snapshot
+    /// is cheap and can be compiled on another thread while this world is reused.
+    pub fn snapshot(&self, source_text: &str) -> Self {
+        let mut world = self.fork(Arc::clone(&self.library));
+        world.update_source(source_text);
+        world
     }
 
-    fn book(&self) -> &LazyHash<FontBook> {
-        &self.font_book
+    /// Create a world with the same fonts, sources and settings but another library
+    fn fork(&self, library: Arc<LazyHash<Library>>) -> Self {
+        Self {
+            root: self.root.clone(),
+            main_source: self.main_source.clone(),
+            main_id: self.main_id,
+            font_store: self.font_store.clone(),
+            library,
+            source_cache: self.source_cache.clone(),
+            binary_cache: HashMap::new(),
+            package_paths: self.package_paths.clone(),
+            sandbox: self.sandbox.clone(),
+            bytes_read: AtomicU64::new(0),
+            used_packages: Mutex::default(),
+            dependencies: Mutex::default(),
+            limits: self.limits,
+            deadline: None,
+            cancel_token: None,
+            fixed_date: self.fixed_date,
+            render_pool: self.render_pool.clone(),
+            package_registry: self.package_registry.clone(),
+            package_archives: Arc::clone(&self.package_archives),
+            checked_archives: Mutex::default(),
+            validated_packages: Mutex::default(),
+        }
     }
 
-    fn main(&self) -> FileId {
-        self.main_id
+    /// Download packages missing from the package path from `registry`
+    ///
+    /// Creates the cache directory if needed. `None` disables downloading.
+    pub fn set_package_registry(
+        &mut self,
+        registry: Option<PackageRegistry>,
+    ) -> Result<(), String> {
+        if let Some(mut registry) = registry {
+            registry.cache_path = fs::create_dir_all(&registry.cache_path)
+                .and_then(|_| registry.cache_path.canonicalize())
+                .map_err(|e| {
+                    format!(
+                        "Failed to create package cache {}: {}",
+                        registry.cache_path.display(),
+                        e
+                    )
+                })?;
+            self.package_registry = Some(registry);
+        } else {
+            self.package_registry = None;
+        }
+        Ok(())
     }
 
-    fn source(&self, id: FileId) -> FileResult<Source> {
-        // Check if this is the main source
-        if id == self.main_id {
-            return Ok(self.main_source.clone());
-        };
+    /// Restrict filesystem access for subsequent compilations
+    pub fn set_sandbox(&mut self, sandbox: SandboxPolicy) {
+        self.sandbox = sandbox;
+    }
 
-        // Check cache first
-        if let Some(source) = self.source_cache.get(&id) {
-            return Ok(source.clone());
-        }
+    /// Resolve a file id to a canonical path on disk.
+    ///
+    /// Both the configured roots and the target are canonicalized, so `..`
+    /// segments and symlinks are resolved before the containment check.
+    /// Anything that ends up outside its root yields `FileError::AccessDenied`.
+    pub fn resolve_path(&self, id: FileId) -> FileResult<PathBuf> {
+        match id.package() {
+            // The file is a part of a package (@preview, etc.)
+            Some(spec) => match self.locate_package(spec)? {
+                Some(PackageLocation::Directory(pkg_dir)) => {
+                    let path = pkg_dir.join(id.vpath().as_rootless_path());
 
-        // Otherwise, it's an external typ file from filesystem
-        let path = self.resolve_path(id)?;
-        let text = fs::read_to_string(&path).map_err(|e| FileError::from_io(e, &path))?;
-        let source = Source::new(id, text);
+                    contained_path(&pkg_dir, &path)
+                }
+                // No package path configured, but user tried to use a package
+                _ if self.package_roots().next().is_none() => Err(FileError::AccessDenied),
+                _ => Err(FileError::Package(PackageError::NotFound(spec.clone()))),
+            },
+            None => {
+                let path = self.root.join(id.vpath().as_rootless_path());
 
-        // in the future we'll insert into cache here,
-        // but since self is &self, we'll need interior mutability (RefCell/DashMap)
-        // but for now, just reading is fine
-        Ok(source)
+                contained_path(&self.root, &path)
+            }
+        }
     }
 
-    fn file(&self, id: FileId) -> FileResult<Bytes> {
-        let path = self.resolve_path(id)?;
-
-        let bytes_vec = fs::read(&path).map_err(|err| FileError::from_io(err, &path))?;
+    /// Pin the current date to a Unix timestamp (in seconds), or use the clock if `None`
+    ///
+    /// Makes `datetime.today()` and the PDF creation date reproducible.
+    pub fn set_fixed_date(&mut self, unix_seconds: Option<i64>) -> Result<(), String> {
+        self.fixed_date = unix_seconds
+            .map(|seconds| {
+                OffsetDateTime::from_unix_timestamp(seconds)
+                    .map_err(|e| format!("Invalid fixed date timestamp {}: {}", seconds, e))
+            })
+            .transpose()?;
+        Ok(())
+    }
 
-        Ok(Bytes::new(bytes_vec))
+    /// Render pages of documents compiled from now on with `threads` threads
+    ///
+    /// `0` uses the global pool, which has one thread per CPU core.
+    pub fn set_render_threads(&mut self, threads: usize) -> Result<(), String> {
+        self.render_pool = if threads == 0 {
+            None
+        } else {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(|index| format!("typst-render-{}", index))
+                .build()
+                .map_err(|e| format!("Failed to create render thread pool: {}", e))?;
+            Some(Arc::new(pool))
+        };
+        Ok(())
     }
 
-    fn font(&self, index: usize) -> Option<Font> {
-        self.fonts.fonts.get(index).and_then(|slot| slot.get())
+    /// Cap time, page count and output size of subsequent compilations
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
     }
 
-    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
-        use time::OffsetDateTime;
+    /// Fails once the current compilation is cancelled or past its deadline
+    fn check_interrupted(&self) -> FileResult<()> {
+        if self
+            .cancel_token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return Err(FileError::Other(Some("compilation was cancelled".into())));
+        }
 
-        if let Some(offset_hours) = offset {
-            // Apply UTC offset in hours
-            let offset = time::UtcOffset::from_hms(offset_hours as i8, 0, 0).ok()?;
-            let now = OffsetDateTime::now_utc().to_offset(offset);
-
-            Datetime::from_ymd_hms(
-                now.year(),
-                now.month() as u8,
-                now.day(),
-                now.hour(),
-                now.minute(),
-                now.second(),
-            )
-        } else {
-            // Local time (auto offset)
-            let now = OffsetDateTime::now_local().ok()?;
-
-            Datetime::from_ymd_hms(
-                now.year(),
-                now.month() as u8,
-                now.day(),
-                now.hour(),
-                now.minute(),
-                now.second(),
-            )
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() > deadline)
+        {
+            return Err(FileError::Other(Some(
+                "compilation time limit exceeded".into(),
+            )));
         }
+
+        Ok(())
     }
-}
 
-// ============================================================================
-// DOCUMENT RENDERING
-// ============================================================================
+    /// Read a file from disk, enforcing the sandbox policy
+    fn read_file(&self, id: FileId) -> FileResult<Vec<u8>> {
+        self.check_interrupted()?;
 
-impl BackendDocument {
-    pub fn page_count(&self) -> usize {
-        self.inner.pages.len()
+        if self.sandbox.forbid_filesystem {
+            return Err(sandbox_error(
+                "filesystem access is disabled by the sandbox policy",
+            ));
+        }
+
+        if let Some(spec) = id.package() {
+            self.validate_package(spec)?;
+            self.record_package(spec)?;
+        }
+
+        self.read_unvalidated(id)
     }
 
-    /// Render a single page to SVG
-    pub fn render_page_svg(&self, page_index: usize) -> Result<Vec<u8>, String> {
-        if page_index >= self.inner.pages.len() {
-            return Err(format!(
-                "Page index {} out of bounds (document has {} pages)",
-                page_index,
-                self.inner.pages.len()
-            ));
+    /// Read a file from a package archive or disk, without validating its package
+    fn read_unvalidated(&self, id: FileId) -> FileResult<Vec<u8>> {
+        if let Some(spec) = id.package()
+            && let Some(archive) = self.package_archive(spec)?
+        {
+            self.record_dependency(&archive.path);
+            let path = id.vpath().as_rootless_path();
+            self.check_extension(path)?;
+
+            let bytes = archive
+                .files
+                .get(path)
+                .ok_or_else(|| FileError::NotFound(id.vpath().as_rooted_path().to_path_buf()))?;
+            self.check_file_size(bytes.len() as u64)?;
+            self.reserve_read_budget(bytes.len() as u64)?;
+
+            return Ok(bytes.clone());
         }
 
-        let page: &Page = &self.inner.pages[page_index];
-        let svg_string = svg(page);
+        // Missing files are dependencies too, creating one changes the output
+        let path = match self.resolve_path(id) {
+            Ok(path) => path,
+            Err(FileError::NotFound(path)) => {
+                self.record_dependency(&path);
+                return Err(FileError::NotFound(path));
+            }
+            Err(e) => return Err(e),
+        };
+        self.record_dependency(&path);
+        self.check_extension(&path)?;
+
+        // Read one byte past the limit instead of trusting the file's metadata,
+        // so a file growing while it is read can't slip past the sandbox
+        let mut bytes = Vec::new();
+        fs::File::open(&path)
+            .and_then(|file| {
+                file.take(self.read_limit().saturating_add(1))
+                    .read_to_end(&mut bytes)
+            })
+            .map_err(|e| FileError::from_io(e, &path))?;
+        self.check_file_size(bytes.len() as u64)?;
+        self.reserve_read_budget(bytes.len() as u64)?;
+
+        Ok(bytes)
+    }
 
-        Ok(svg_string.into_bytes())
+    /// Remember that the current compilation read or looked for `path`
+    fn record_dependency(&self, path: &Path) {
+        let mut dependencies = self
+            .dependencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !dependencies.iter().any(|dependency| dependency == path) {
+            dependencies.push(path.to_path_buf());
+        }
     }
 
-    /// Render all pages to SVG
-    pub fn render_all_pages_svg(&self) -> Result<Vec<Vec<u8>>, String> {
-        if self.inner.pages.is_empty() {
-            return Err("Document has no pages to render".to_string());
+    /// Files the last compilation on this world requested from disk, in order of first request
+    ///
+    /// Includes files that were missing or could not be read. Paths are
+    /// canonical, missing files are reported where they would be created.
+    /// Files of packages loaded from an archive are reported as the archive.
+    /// The main source is not included.
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.dependencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Fails if the sandbox does not allow the file type of `path`
+    fn check_extension(&self, path: &Path) -> FileResult<()> {
+        if self.sandbox.allowed_extensions.is_empty() {
+            return Ok(());
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+
+        if !self.sandbox.allowed_extensions.contains(&extension) {
+            return Err(sandbox_error(&format!(
+                "file type \".{}\" is not allowed by the sandbox policy",
+                extension
+            )));
         }
+        Ok(())
+    }
 
-        let mut results = Vec::with_capacity(self.inner.pages.len());
+    /// Most bytes a single read may return before breaking a sandbox size limit
+    fn read_limit(&self) -> u64 {
+        let file_limit = match self.sandbox.max_file_size {
+            0 => u64::MAX,
+            max => max,
+        };
+        let budget_left = match self.sandbox.max_total_bytes {
+            0 => u64::MAX,
+            max => max.saturating_sub(self.bytes_read.load(Ordering::Relaxed)),
+        };
+        file_limit.min(budget_left)
+    }
 
-        for page in &self.inner.pages {
-            let svg_string = svg(page);
-            results.push(svg_string.into_bytes());
+    /// Fails if a file of `size` bytes breaks the sandbox file size limit
+    fn check_file_size(&self, size: u64) -> FileResult<()> {
+        if self.sandbox.max_file_size > 0 && size > self.sandbox.max_file_size {
+            return Err(sandbox_error(&format!(
+                "file exceeds the sandbox limit of {} bytes",
+                self.sandbox.max_file_size
+            )));
         }
+        Ok(())
+    }
 
-        Ok(results)
+    /// Count `size` more bytes against the compilation's read budget
+    ///
+    /// Checking and adding is one atomic step, so concurrent reads of the
+    /// same compilation can't exceed the budget together.
+    fn reserve_read_budget(&self, size: u64) -> FileResult<()> {
+        let max_total = self.sandbox.max_total_bytes;
+        self.bytes_read
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |read| {
+                let total = read.saturating_add(size);
+                (max_total == 0 || total <= max_total).then_some(total)
+            })
+            .map(|_| ())
+            .map_err(|_| {
+                sandbox_error(&format!(
+                    "total bytes read would exceed the sandbox limit of {} bytes",
+                    max_total
+                ))
+            })
     }
 
-    /// Render entire document to PDF
-    pub fn render_pdf(&self) -> Result<Vec<u8>, String> {
-        let options = PdfOptions::default();
+    /// Package roots in lookup order: the package paths, then the registry cache
+    fn package_roots(&self) -> impl Iterator<Item = &PathBuf> {
+        let cache_path = self.package_registry.as_ref().map(|r| &r.cache_path);
+        self.package_paths.iter().chain(cache_path)
+    }
 
-        match pdf(&self.inner, &options) {
-            Ok(bytes) => Ok(bytes.into()),
-            Err(errors) => {
-                let error_msg = errors
-                    .iter()
-                    .map(|e| e.message.to_string())
-                    .collect::<Vec<_>>()
-                    .join("; ");
-                Err(format!("PDF rendering failed: {}", error_msg))
+    /// Workspace root (canonical)
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Find the first package root with the package, either unpacked as
+    /// `namespace/name/version` or archived as `namespace/name-version.tar.gz`
+    fn locate_package(&self, spec: &PackageSpec) -> FileResult<Option<PackageLocation>> {
+        let archive_name = format!("{}-{}.tar.gz", spec.name, spec.version);
+
+        for root in self.package_roots() {
+            let namespace_dir = root.join(spec.namespace.as_str());
+
+            let unpacked = namespace_dir
+                .join(spec.name.as_str())
+                .join(spec.version.to_string());
+            if unpacked.is_dir() {
+                return contained_path(root, &unpacked)
+                    .map(|path| Some(PackageLocation::Directory(path)));
+            }
+
+            let archive = namespace_dir.join(&archive_name);
+            if archive.is_file() {
+                return contained_path(root, &archive)
+                    .map(|path| Some(PackageLocation::Archive(path)));
             }
         }
+
+        Ok(None)
     }
-}
 
-// ============================================================================
-// HELPER FUNCTIONS
-// ============================================================================
+    /// Check a package's `typst.toml` the first time one of its files is read
+    ///
+    /// The manifest must exist and parse, match the imported name and version,
+    /// accept this compiler version and point to an existing entrypoint. Errors
+    /// name the offending package.
+    ///
+    /// The lock is not held while the package is read, so a concurrent read of
+    /// the same package may validate it again.
+    fn validate_package(&self, spec: &PackageSpec) -> FileResult<()> {
+        if self
+            .validated_packages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(spec)
+        {
+            return Ok(());
+        }
 
-/// Converts typst's SourceDiagnostic to our BackendDiagnostic
-fn convert_diagnostic(diag: &SourceDiagnostic, world: &BackendWorld) -> BackendDiagnostic {
-    let severity = match diag.severity {
-        typst::diag::Severity::Error => DiagnosticSeverity::Error,
-        typst::diag::Severity::Warning => DiagnosticSeverity::Warning,
-    };
+        let invalid = |message: &str| {
+            FileError::Package(PackageError::Other(Some(eco_format!(
+                "{}: {}", spec, message
+            ))))
+        };
 
-    // Format message including hints
-    let mut message = diag.message.to_string();
-    for hint in &diag.hints {
-        message.push_str("\nHint: ");
-        message.push_str(&hint.to_string());
-    }
-
-    let mut location = None;
-    let span = diag.span;
-
-    if let Some(id) = span.id() {
-        if let Ok(source) = world.source(id) {
-            if let Some(range) = source.range(span) {
-                let lines = source.lines();
-
-                // Note: Typst indices are 0-based; .NET is 1-based.
-                let line = lines
-                    .byte_to_line(range.start)
-                    .map(|l| l as u32 + 1)
-                    .unwrap_or(0);
-                let col = lines
-                    .byte_to_column(range.start)
-                    .map(|c| c as u32 + 1)
-                    .unwrap_or(0);
-                let length = (range.end - range.start) as u32;
-
-                if line > 0 {
-                    location = Some(BackendLocation {
-                        line,
-                        column: col,
-                        length,
-                    });
-                }
+        let manifest_id = FileId::new(Some(spec.clone()), VirtualPath::new(MANIFEST_FILE));
+        let manifest_bytes = match self.read_unvalidated(manifest_id) {
+            Ok(bytes) => bytes,
+            Err(FileError::NotFound(_)) => {
+                return Err(invalid("package has no typst.toml manifest"));
             }
+            Err(e) => return Err(e),
+        };
+
+        let manifest: PackageManifest = std::str::from_utf8(&manifest_bytes)
+            .map_err(|_| invalid("package manifest is not valid UTF-8"))
+            .and_then(|text| {
+                toml::from_str(text).map_err(|e| {
+                    invalid(&format!("package manifest is malformed ({})", e.message()))
+                })
+            })?;
+        manifest.validate(spec).map_err(|e| invalid(&e))?;
+
+        let entry_id = manifest_id.join(&manifest.package.entrypoint);
+        let entry_exists = match self.package_archive(spec)? {
+            Some(archive) => archive
+                .files
+                .contains_key(entry_id.vpath().as_rootless_path()),
+            None => self.resolve_path(entry_id).is_ok_and(|path| path.is_file()),
+        };
+        if !entry_exists {
+            return Err(invalid(&format!(
+                "package entrypoint `{}` does not exist",
+                manifest.package.entrypoint
+            )));
         }
+
+        self.validated_packages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(spec.clone());
+        Ok(())
     }
 
-    BackendDiagnostic {
-        severity,
-        message,
-        location,
+    /// Remember that the current compilation uses a package
+    fn record_package(&self, spec: &PackageSpec) -> FileResult<()> {
+        let mut used = self
+            .used_packages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let recorded = used.iter().any(|package| {
+            package.namespace == spec.namespace.as_str()
+                && package.name == spec.name.as_str()
+                && package.version == spec.version.to_string()
+        });
+        if recorded {
+            return Ok(());
+        }
+
+        let (path, archive) = match self.locate_package(spec)? {
+            Some(PackageLocation::Directory(path)) => (path, false),
+            Some(PackageLocation::Archive(path)) => (path, true),
+            None => return Ok(()),
+        };
+
+        used.push(BackendPackageUsage {
+            namespace: spec.namespace.to_string(),
+            name: spec.name.to_string(),
+            version: spec.version.to_string(),
+            path,
+            archive,
+        });
+        Ok(())
     }
-}
 
-/// Converts serde_json::Value to typst::Value recursively
-fn json_to_typst(json: JsonValue) -> Value {
-    match json {
-        JsonValue::Null => Value::None,
-        JsonValue::Bool(b) => Value::Bool(b),
-        JsonValue::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Value::Int(i)
-            } else {
-                Value::Float(n.as_f64().unwrap_or(0.0))
-            }
+    /// Get the archive of a package that is not unpacked in a package root
+    ///
+    /// Archives are decompressed on first access and kept in the shared cache.
+    /// The first access of every compilation checks the cached archive against
+    /// its file's path, modification time and length, and reads it again if
+    /// it was replaced. Packages found nowhere are downloaded from the
+    /// registry, except for the `@local` namespace.
+    ///
+    /// Only the archive's own slot is locked while it is opened or downloaded,
+    /// so other packages stay available meanwhile.
+    fn package_archive(&self, spec: &PackageSpec) -> FileResult<Option<Arc<PackageArchive>>> {
+        if let Some(archive) = self
+            .checked_archives
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(spec)
+        {
+            return Ok(Some(Arc::clone(archive)));
         }
-        JsonValue::String(s) => Value::Str(s.into()),
-        JsonValue::Array(a) => Value::Array(a.into_iter().map(json_to_typst).collect()),
-        JsonValue::Object(o) => {
-            let mut dict = Dict::new();
-            for (k, v) in o {
-                dict.insert(k.into(), json_to_typst(v));
+
+        let slot = self
+            .package_archives
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .slot(spec);
+        let mut cached = slot.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let archive = match self.locate_package(spec)? {
+            Some(PackageLocation::Directory(_)) => {
+                // Unpacked since, the archive is of no use anymore
+                if cached.take().is_some() {
+                    self.package_archives
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .loaded(spec, &slot, 0);
+                }
+                return Ok(None);
             }
-            Value::Dict(dict)
+            Some(PackageLocation::Archive(path)) => match cached.as_ref() {
+                Some(archive) if archive.is_current(&path) => Arc::clone(archive),
+                _ => Arc::new(
+                    PackageArchive::open(&path, self.sandbox.max_total_bytes).map_err(|e| {
+                        FileError::Package(PackageError::MalformedArchive(Some(e.into())))
+                    })?,
+                ),
+            },
+            None => match self.package_registry {
+                Some(ref registry) if spec.namespace != LOCAL_NAMESPACE => {
+                    Arc::new(registry.download(spec, self.sandbox.max_total_bytes, || {
+                        self.check_interrupted()
+                    })?)
+                }
+                _ => return Ok(None),
+            },
+        };
+
+        if !cached
+            .as_ref()
+            .is_some_and(|previous| Arc::ptr_eq(previous, &archive))
+        {
+            *cached = Some(Arc::clone(&archive));
+            self.package_archives
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .loaded(spec, &slot, archive.size());
         }
-    }
-}
 
-// ============================================================================
-// VERSION COMPATIBILITY
-// ============================================================================
+        self.checked_archives
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(spec.clone(), Arc::clone(&archive));
+        Ok(Some(archive))
+    }
 
-/// Compile-time check that we're using the expected typst version.
-#[cfg(test)]
-mod version_tests {
-    /// This will be removed later.
-    #[test]
-    fn verify_typst_version() {
-        const EXPECTED_VERSION: &str = "0.14.2";
-        const ACTUAL_VERSION: &str = env!("TYPST_VERSION");
+    pub fn compile(&mut self) -> BackendCompileResult {
+        self.compile_impl(None)
+    }
 
-        assert_eq!(
-            ACTUAL_VERSION, EXPECTED_VERSION,
-            "typst version changed! expected {}, found {}. update typst_backend.rs if needed.",
-            EXPECTED_VERSION, ACTUAL_VERSION
-        );
+    /// Compile, returning as soon as `cancel_token` is triggered (see `run_compile`)
+    pub fn compile_with_cancel(
+        &mut self,
+        cancel_token: Arc<CancellationToken>,
+    ) -> BackendCompileResult {
+        self.compile_impl(Some(cancel_token))
     }
 
-    #[test]
-    fn verify_compile_signature() {
-        // compile-time check: this won't compile if typst::compile signature changes
-        fn _assert_compile_signature<W: typst::World>(world: &W) {
-            let _: typst::diag::Warned<typst::diag::SourceResult<typst::layout::PagedDocument>> =
-                typst::compile::<typst::layout::PagedDocument>(world);
+    fn compile_impl(
+        &mut self,
+        cancel_token: Option<Arc<CancellationToken>>,
+    ) -> BackendCompileResult {
+        if cancel_token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return cancelled_result(Vec::new());
         }
-    }
-}
 
-#[cfg(test)]
-mod vfs_tests {
-    use super::*;
-    use std::env;
-    use std::fs;
+        self.bytes_read.store(0, Ordering::Relaxed);
+        self.used_packages
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.dependencies
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        // Packages may be edited or replaced between compilations
+        self.checked_archives
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.validated_packages
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.cancel_token = cancel_token;
+
+        let started = Instant::now();
+        let time_limit = Duration::from_millis(self.limits.max_compile_time_ms);
+        self.deadline = (self.limits.max_compile_time_ms > 0).then(|| started + time_limit);
+
+        let warned = self.run_compile();
+
+        // Diagnostics below re-read sources, which must not be interrupted
+        self.deadline = None;
+        let cancel_token = self.cancel_token.take();
+
+        let warned = match warned {
+            Ok(warned) => warned,
+            Err(result) => return *result,
+        };
 
-    #[test]
-    fn test_file_read_simple() {
-        let temp_dir = env::temp_dir().join("typst_vfs_test");
-        fs::create_dir_all(&temp_dir).unwrap();
+        // Extract diagnostics (warnings always present)
+        let warnings: Vec<BackendDiagnostic> = warned
+            .warnings
+            .iter()
+            .map(|diag| convert_diagnostic(diag, self))
+            .collect();
 
-        let test_file = temp_dir.join("data.txt");
-        fs::write(&test_file, b"Hello, VFS!").unwrap();
+        if cancel_token.is_some_and(|token| token.is_cancelled()) {
+            return cancelled_result(warnings);
+        }
 
-        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+        if self.deadline_elapsed(started) {
+            return self.time_limit_result(warnings);
+        }
 
-        world.update_source(
-            r#"#let data = read("data.txt")
-                          Data: #data"#,
-        );
+        match warned.output {
+            Ok(document) => {
+                let page_count = document.pages.len() as u64;
+                if self.limits.max_pages > 0 && page_count > self.limits.max_pages {
+                    return aborted_result(
+                        AbortReason::PageLimit,
+                        format!(
+                            "Document has {} pages, exceeding the limit of {} pages",
+                            page_count, self.limits.max_pages
+                        ),
+                        warnings,
+                    );
+                }
 
-        let result = world.compile();
+                BackendCompileResult {
+                    success: true,
+                    document: Some(BackendDocument {
+                        inner: document,
+                        max_output_bytes: self.limits.max_output_bytes,
+                        timestamp: self.fixed_date.and_then(to_typst_datetime),
+                        render_pool: self.render_pool.clone(),
+                        packages: std::mem::take(
+                            self.used_packages
+                                .get_mut()
+                                .unwrap_or_else(PoisonError::into_inner),
+                        ),
+                    }),
+                    diagnostics: warnings,
+                    aborted: None,
+                }
+            }
+            Err(errors) => {
+                let mut all_diagnostics: Vec<BackendDiagnostic> = errors
+                    .iter()
+                    .map(|diag| convert_diagnostic(diag, self))
+                    .collect();
+                all_diagnostics.extend(warnings);
 
-        // Cleanup
-        fs::remove_dir_all(temp_dir).ok();
+                BackendCompileResult {
+                    success: false,
+                    document: None,
+                    diagnostics: all_diagnostics,
+                    aborted: None,
+                }
+            }
+        }
+    }
 
-        assert!(result.success, "Compilation should succeed")
+    /// Run typst on this world, on the compile pool if the compilation can be interrupted
+    ///
+    /// Fails once the deadline passes or the cancellation token is triggered,
+    /// abandoning the compilation: typst has no way to stop evaluation or
+    /// layout, so the caller gets control back right away but the pool worker
+    /// (and its CPU core) stays busy until typst finishes. The world is forked
+    /// for the worker, and replaced with it once it finishes.
+    fn run_compile(
+        &mut self,
+    ) -> Result<Warned<SourceResult<PagedDocument>>, Box<BackendCompileResult>> {
+        // Already on a pool worker: whoever waits for this
+        // job abandons it, and file access still checks the deadline
+        if (self.deadline.is_none() && self.cancel_token.is_none()) || compile_pool::on_worker() {
+            return Ok(typst::compile(self));
+        }
+
+        let mut worker = self.fork(Arc::clone(&self.library));
+        worker.deadline = self.deadline;
+        worker.cancel_token = self.cancel_token.clone();
+
+        let job = compile_pool::submit(move || {
+            let warned = typst::compile::<PagedDocument>(&worker);
+            (warned, worker)
+        })
+        .map_err(|e| Box::new(failed_result(e)))?;
+
+        match job.wait(INTERRUPT_POLL, || self.check_interrupted().is_err()) {
+            Some((warned, worker)) => {
+                *self = worker;
+                Ok(warned)
+            }
+            None if self
+                .cancel_token
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled()) =>
+            {
+                Err(Box::new(cancelled_result(Vec::new())))
+            }
+            None => Err(Box::new(self.time_limit_result(Vec::new()))),
+        }
     }
 
-    #[test]
-    fn test_file_not_found() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    /// Builds the result of a compilation that ran past the time limit
+    fn time_limit_result(&self, warnings: Vec<BackendDiagnostic>) -> BackendCompileResult {
+        aborted_result(
+            AbortReason::TimeLimit,
+            format!(
+                "Compilation exceeded the time limit of {} ms",
+                self.limits.max_compile_time_ms
+            ),
+            warnings,
+        )
+    }
 
-        // Try to read non-existent file
-        world.update_source(r#"#read("doesnotexist.txt")"#);
-        let result = world.compile();
+    /// Whether a compilation started at `started` ran past the time limit
+    fn deadline_elapsed(&self, started: Instant) -> bool {
+        self.limits.max_compile_time_ms > 0
+            && started.elapsed() > Duration::from_millis(self.limits.max_compile_time_ms)
+    }
+}
 
-        // Should fail
-        assert!(!result.success);
-        assert!(!result.diagnostics.is_empty());
+impl World for BackendWorld {
+    fn library(&self) -> &LazyHash<Library> {
+        &self.library
     }
 
-    #[test]
-    fn test_import_statement() {
-        let temp_dir = env::temp_dir().join("typst_import_test");
-        fs::create_dir_all(&temp_dir).unwrap();
+    fn book(&self) -> &LazyHash<FontBook> {
+        &self.font_store.book
+    }
 
-        let module_file = temp_dir.join("helper.typ");
-        fs::write(&module_file, b"#let greet(name) = \"Hello, \" + name").unwrap();
+    fn main(&self) -> FileId {
+        self.main_id
+    }
 
-        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        self.check_interrupted()?;
 
-        // Compile with import
-        world.update_source(
-            r#"#import "helper.typ": greet
-                        #greet("World")"#,
-        );
+        // Check if this is the main source
+        if id == self.main_id {
+            return Ok(self.main_source.clone());
+        };
 
-        let result = world.compile();
+        // Check cache first
+        if let Some(source) = self.source_cache.get(&id) {
+            return Ok(source.clone());
+        }
 
-        // Cleanup
-        fs::remove_dir_all(&temp_dir).ok();
+        // Otherwise, it's an external typ file from filesystem
+        let text = String::from_utf8(self.read_file(id)?)?;
+        let source = Source::new(id, text);
 
-        assert!(result.success, "Import should work");
+        // in the future we'll insert into cache here,
+        // but since self is &self, we'll need interior mutability (RefCell/DashMap)
+        // but for now, just reading is fine
+        Ok(source)
     }
 
-    #[test]
-    fn test_read_json() {
-        let temp_dir = env::temp_dir().join("typst_json_test");
-        fs::create_dir_all(&temp_dir).unwrap();
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        Ok(Bytes::new(self.read_file(id)?))
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.font_store.font(index)
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        let now = match (self.fixed_date, offset) {
+            // Apply UTC offset in hours
+            (Some(fixed), Some(offset_hours)) => {
+                fixed.to_offset(UtcOffset::from_hms(offset_hours as i8, 0, 0).ok()?)
+            }
+            // Fixed dates are UTC unless an offset is requested, so they don't
+            // depend on the machine's timezone
+            (Some(fixed), None) => fixed,
+            (None, Some(offset_hours)) => OffsetDateTime::now_utc()
+                .to_offset(UtcOffset::from_hms(offset_hours as i8, 0, 0).ok()?),
+            // Local time (auto offset)
+            (None, None) => OffsetDateTime::now_local().ok()?,
+        };
+
+        to_typst_datetime(now)
+    }
+}
+
+// ============================================================================
+// SYNTAX CHECKING
+// ============================================================================
+
+/// Parse source text and return only its syntax errors.
+///
+/// Does not need a world: no fonts, library or file access are involved.
+pub fn check_syntax(source_text: &str) -> Vec<BackendDiagnostic> {
+    let source = Source::detached(source_text);
+
+    source
+        .root()
+        .errors()
+        .iter()
+        .map(|error| convert_syntax_error(error, &source))
+        .collect()
+}
+
+// ============================================================================
+// DOCUMENT RENDERING
+// ============================================================================
+
+impl BackendDocument {
+    pub fn page_count(&self) -> usize {
+        self.inner.pages.len()
+    }
+
+    /// Render a single page to SVG
+    pub fn render_page_svg(&self, page_index: usize) -> Result<Vec<u8>, RenderError> {
+        if page_index >= self.inner.pages.len() {
+            return Err(RenderError::Failed(format!(
+                "Page index {} out of bounds (document has {} pages)",
+                page_index,
+                self.inner.pages.len()
+            )));
+        }
+
+        let page: &Page = &self.inner.pages[page_index];
+        let svg_bytes = svg(page).into_bytes();
+        self.check_output_size(svg_bytes.len())?;
+
+        Ok(svg_bytes)
+    }
+
+    /// Render all pages to SVG in parallel
+    ///
+    /// The output limit applies to the combined size of all pages.
+    /// `cancel` is checked before each page.
+    pub fn render_all_pages_svg(
+        &self,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<Vec<u8>>, RenderError> {
+        self.render_pages(cancel, |page| Ok(svg(page).into_bytes()))
+    }
+
+    /// Packages read from while compiling the document, in order of first use
+    pub fn packages(&self) -> &[BackendPackageUsage] {
+        &self.packages
+    }
+
+    /// Collect the fonts used on each page and the characters without a glyph
+    pub fn font_report(&self) -> BackendFontReport {
+        let mut report = BackendFontReport::default();
+        // Same order as `report.fonts`; documents use few fonts, so a list is enough
+        let mut seen_fonts: Vec<Font> = Vec::new();
+        let mut glyph_slots: HashMap<String, usize> = HashMap::new();
+
+        for (page_index, page) in self.inner.pages.iter().enumerate() {
+            visit_text(&page.frame, &mut |text| {
+                let slot = match seen_fonts.iter().position(|font| *font == text.font) {
+                    Some(slot) => slot,
+                    None => {
+                        let info = text.font.info();
+                        report.fonts.push(BackendFontUsage {
+                            family: info.family.clone(),
+                            style: style_name(info.variant.style),
+                            weight: info.variant.weight.to_number(),
+                            pages: Vec::new(),
+                        });
+                        seen_fonts.push(text.font.clone());
+                        seen_fonts.len() - 1
+                    }
+                };
+                push_page(&mut report.fonts[slot].pages, page_index);
+
+                // Glyph 0 is `.notdef`, drawn when no font has the character
+                for glyph in text.glyphs.iter().filter(|glyph| glyph.id == 0) {
+                    let missing = &text.text[glyph.range()];
+                    let slot = *glyph_slots.entry(missing.to_string()).or_insert_with(|| {
+                        report.missing_glyphs.push(BackendMissingGlyph {
+                            text: missing.to_string(),
+                            font_family: text.font.info().family.clone(),
+                            pages: Vec::new(),
+                        });
+                        report.missing_glyphs.len() - 1
+                    });
+                    push_page(&mut report.missing_glyphs[slot].pages, page_index);
+                }
+            });
+        }
+
+        report
+    }
+
+    /// Render a single page to PNG at `pixels_per_pt` (1.0 = 72 DPI)
+    pub fn render_page_png(
+        &self,
+        page_index: usize,
+        pixels_per_pt: f32,
+    ) -> Result<Vec<u8>, RenderError> {
+        let page = self.inner.pages.get(page_index).ok_or_else(|| {
+            RenderError::Failed(format!(
+                "Page index {} out of bounds (document has {} pages)",
+                page_index,
+                self.inner.pages.len()
+            ))
+        })?;
+
+        let png_bytes = render_png(page, pixels_per_pt)?;
+        self.check_output_size(png_bytes.len())?;
+
+        Ok(png_bytes)
+    }
+
+    /// Render all pages to PNG in parallel at `pixels_per_pt` (1.0 = 72 DPI)
+    ///
+    /// Same output limit and cancellation semantics as `render_all_pages_svg`.
+    pub fn render_all_pages_png(
+        &self,
+        pixels_per_pt: f32,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<Vec<u8>>, RenderError> {
+        self.render_pages(cancel, |page| render_png(page, pixels_per_pt))
+    }
+
+    /// Render every page with `render` on the render pool, keeping page order
+    ///
+    /// Stops early once cancelled or once the combined size exceeds the output limit.
+    fn render_pages<F>(
+        &self,
+        cancel: Option<&CancellationToken>,
+        render: F,
+    ) -> Result<Vec<Vec<u8>>, RenderError>
+    where
+        F: Fn(&Page) -> Result<Vec<u8>, RenderError> + Sync,
+    {
+        if self.inner.pages.is_empty() {
+            return Err(RenderError::Failed(
+                "Document has no pages to render".to_string(),
+            ));
+        }
+
+        let total_size = AtomicUsize::new(0);
+        let render_all = || {
+            self.inner
+                .pages
+                .par_iter()
+                .map(|page| {
+                    check_cancelled(cancel)?;
+
+                    let bytes = render(page)?;
+                    let total = total_size.fetch_add(bytes.len(), Ordering::Relaxed) + bytes.len();
+                    self.check_output_size(total)?;
+                    Ok(bytes)
+                })
+                .collect()
+        };
+
+        match &self.render_pool {
+            Some(pool) => pool.install(render_all),
+            None => render_all(),
+        }
+    }
+
+    /// Render entire document to PDF
+    ///
+    /// PDF export is a single typst call, so `cancel` is checked before and after it.
+    pub fn render_pdf(&self, cancel: Option<&CancellationToken>) -> Result<Vec<u8>, RenderError> {
+        check_cancelled(cancel)?;
+
+        let options = PdfOptions {
+            timestamp: self.timestamp.map(Timestamp::new_utc),
+            ..PdfOptions::default()
+        };
+
+        match pdf(&self.inner, &options) {
+            Ok(bytes) => {
+                check_cancelled(cancel)?;
+                self.check_output_size(bytes.len())?;
+                Ok(bytes)
+            }
+            Err(errors) => {
+                let error_msg = errors
+                    .iter()
+                    .map(|e| e.message.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                Err(RenderError::Failed(format!(
+                    "PDF rendering failed: {}",
+                    error_msg
+                )))
+            }
+        }
+    }
+
+    /// Fails if `size` exceeds the output limit
+    fn check_output_size(&self, size: usize) -> Result<(), RenderError> {
+        let size = size as u64;
+        if self.max_output_bytes > 0 && size > self.max_output_bytes {
+            return Err(RenderError::OutputLimitExceeded {
+                size,
+                limit: self.max_output_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Converts a `time` datetime to typst's Datetime (second precision)
+fn to_typst_datetime(datetime: OffsetDateTime) -> Option<Datetime> {
+    Datetime::from_ymd_hms(
+        datetime.year(),
+        datetime.month() as u8,
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+    )
+}
+
+/// Rasterizes a page through its SVG, so the output matches SVG export
+fn render_png(page: &Page, pixels_per_pt: f32) -> Result<Vec<u8>, RenderError> {
+    if !pixels_per_pt.is_finite() || pixels_per_pt <= 0.0 {
+        return Err(RenderError::Failed(format!(
+            "Invalid pixels per point: {}",
+            pixels_per_pt
+        )));
+    }
+
+    let tree = usvg::Tree::from_str(&svg(page), &usvg::Options::default())
+        .map_err(|e| RenderError::Failed(format!("PNG rendering failed: {}", e)))?;
+
+    // usvg measures in CSS pixels, so size the image from the page in points
+    let size = tree.size();
+    let width = (page.frame.width().to_pt() as f32 * pixels_per_pt)
+        .round()
+        .max(1.0) as u32;
+    let height = (page.frame.height().to_pt() as f32 * pixels_per_pt)
+        .round()
+        .max(1.0) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or_else(|| {
+        RenderError::Failed(format!("Invalid PNG dimensions {}x{}", width, height))
+    })?;
+
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / size.width(),
+        height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    pixmap
+        .encode_png()
+        .map_err(|e| RenderError::Failed(format!("PNG encoding failed: {}", e)))
+}
+
+/// Directory the typst CLI installs `@local` packages to, if it exists
+///
+/// `{data-dir}/typst/packages`, e.g. `~/.local/share/typst/packages` on Linux.
+pub fn user_package_path() -> Option<PathBuf> {
+    typst_kit::package::default_package_path().filter(|path| path.is_dir())
+}
+
+/// Normalizes an archive entry path, rejecting paths that leave the package root
+fn package_relative_path(path: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+/// Calls `f` for every text run in `frame` and its nested groups
+fn visit_text(frame: &Frame, f: &mut impl FnMut(&TextItem)) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => visit_text(&group.frame, f),
+            FrameItem::Text(text) => f(text),
+            _ => {}
+        }
+    }
+}
+
+/// Records `page_index` unless it is already the last recorded page
+fn push_page(pages: &mut Vec<usize>, page_index: usize) {
+    if pages.last() != Some(&page_index) {
+        pages.push(page_index);
+    }
+}
+
+/// Lowercase name of a font style
+fn style_name(style: FontStyle) -> &'static str {
+    match style {
+        FontStyle::Normal => "normal",
+        FontStyle::Italic => "italic",
+        FontStyle::Oblique => "oblique",
+    }
+}
+
+/// Collapses a font's coverage into inclusive codepoint ranges
+fn coverage_ranges(coverage: &Coverage) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for codepoint in coverage.iter() {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == codepoint => *end = codepoint,
+            _ => ranges.push((codepoint, codepoint)),
+        }
+    }
+    ranges
+}
+
+/// Fails with `RenderError::Cancelled` once `cancel` is triggered
+fn check_cancelled(cancel: Option<&CancellationToken>) -> Result<(), RenderError> {
+    if cancel.is_some_and(|token| token.is_cancelled()) {
+        return Err(RenderError::Cancelled);
+    }
+    Ok(())
+}
+
+/// Builds a failed result for a cancelled compilation
+fn cancelled_result(diagnostics: Vec<BackendDiagnostic>) -> BackendCompileResult {
+    aborted_result(
+        AbortReason::Cancelled,
+        "Compilation was cancelled".to_string(),
+        diagnostics,
+    )
+}
+
+/// Builds a failed result that never reached compilation
+fn failed_result(message: String) -> BackendCompileResult {
+    BackendCompileResult {
+        success: false,
+        document: None,
+        diagnostics: vec![BackendDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            message,
+            location: None,
+            file: None,
+        }],
+        aborted: None,
+    }
+}
+
+/// Builds a failed result for a compilation stopped before completion
+fn aborted_result(
+    reason: AbortReason,
+    message: String,
+    diagnostics: Vec<BackendDiagnostic>,
+) -> BackendCompileResult {
+    let mut all_diagnostics = vec![BackendDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        message,
+        location: None,
+        file: None,
+    }];
+    all_diagnostics.extend(diagnostics);
+
+    BackendCompileResult {
+        success: false,
+        document: None,
+        diagnostics: all_diagnostics,
+        aborted: Some(reason),
+    }
+}
+
+/// Creates a FileError describing a sandbox violation
+fn sandbox_error(message: &str) -> FileError {
+    FileError::Other(Some(message.into()))
+}
+
+/// Canonicalizes `path` and ensures it stays inside the (canonical) `root`
+fn contained_path(root: &Path, path: &Path) -> FileResult<PathBuf> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| FileError::from_io(e, path))?;
+
+    if !canonical.starts_with(root) {
+        return Err(FileError::AccessDenied);
+    }
+
+    Ok(canonical)
+}
+
+/// Converts typst's SourceDiagnostic to our BackendDiagnostic
+fn convert_diagnostic(diag: &SourceDiagnostic, world: &BackendWorld) -> BackendDiagnostic {
+    let severity = match diag.severity {
+        typst::diag::Severity::Error => DiagnosticSeverity::Error,
+        typst::diag::Severity::Warning => DiagnosticSeverity::Warning,
+    };
+
+    let location = diag
+        .span
+        .id()
+        .and_then(|id| world.source(id).ok())
+        .and_then(|source| span_location(&source, diag.span));
+    let file = location.and(diag.span.id()).map(|id| match id.package() {
+        Some(spec) => PathBuf::from(format!("{}{}", spec, id.vpath().as_rooted_path().display())),
+        None => world.root.join(id.vpath().as_rootless_path()),
+    });
+
+    BackendDiagnostic {
+        severity,
+        message: format_message(&diag.message, &diag.hints),
+        location,
+        file,
+    }
+}
+
+/// Converts typst's SyntaxError to our BackendDiagnostic
+fn convert_syntax_error(error: &SyntaxError, source: &Source) -> BackendDiagnostic {
+    BackendDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        message: format_message(&error.message, &error.hints),
+        location: span_location(source, error.span),
+        file: None,
+    }
+}
+
+/// Formats a diagnostic message including hints
+fn format_message(message: &str, hints: &[EcoString]) -> String {
+    let mut message = message.to_string();
+    for hint in hints {
+        message.push_str("\nHint: ");
+        message.push_str(hint);
+    }
+    message
+}
+
+/// Resolves a span to a 1-indexed line/column location within `source`
+fn span_location(source: &Source, span: Span) -> Option<BackendLocation> {
+    let range = source.range(span)?;
+    let lines = source.lines();
+
+    // Note: Typst indices are 0-based; .NET is 1-based.
+    let line = lines
+        .byte_to_line(range.start)
+        .map(|l| l as u32 + 1)
+        .unwrap_or(0);
+    let column = lines
+        .byte_to_column(range.start)
+        .map(|c| c as u32 + 1)
+        .unwrap_or(0);
+    let length = (range.end - range.start) as u32;
+
+    (line > 0).then_some(BackendLocation {
+        line,
+        column,
+        length,
+    })
+}
+
+/// Build the standard library with `sys.inputs` parsed from a JSON object
+fn build_library(inputs_json: Option<&str>) -> Result<LazyHash<Library>, String> {
+    let inputs_val = inputs_json
+        .map(|json| serde_json::from_str(json).map_err(|e| format!("Invalid inputs JSON: {}", e)))
+        .transpose()?
+        .unwrap_or(JsonValue::Null);
+
+    Ok(library_from_json(inputs_val))
+}
+
+/// Build the standard library with `sys.inputs` from a JSON value (non-objects = no inputs)
+fn library_from_json(inputs_val: JsonValue) -> LazyHash<Library> {
+    let inputs = match json_to_typst(inputs_val) {
+        Value::Dict(d) => d,
+        _ => Dict::new(),
+    };
+
+    LazyHash::new(Library::builder().with_inputs(inputs).build())
+}
+
+/// Converts serde_json::Value to typst::Value recursively
+fn json_to_typst(json: JsonValue) -> Value {
+    match json {
+        JsonValue::Null => Value::None,
+        JsonValue::Bool(b) => Value::Bool(b),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::Int(i)
+            } else {
+                Value::Float(n.as_f64().unwrap_or(0.0))
+            }
+        }
+        JsonValue::String(s) => Value::Str(s.into()),
+        JsonValue::Array(a) => Value::Array(a.into_iter().map(json_to_typst).collect()),
+        JsonValue::Object(o) => {
+            let mut dict = Dict::new();
+            for (k, v) in o {
+                dict.insert(k.into(), json_to_typst(v));
+            }
+            Value::Dict(dict)
+        }
+    }
+}
+
+// ============================================================================
+// VERSION COMPATIBILITY
+// ============================================================================
+
+/// Compile-time check that we're using the expected typst version.
+#[cfg(test)]
+mod version_tests {
+    /// This will be removed later.
+    #[test]
+    fn verify_typst_version() {
+        const EXPECTED_VERSION: &str = "0.14.2";
+        const ACTUAL_VERSION: &str = env!("TYPST_VERSION");
+
+        assert_eq!(
+            ACTUAL_VERSION, EXPECTED_VERSION,
+            "typst version changed! expected {}, found {}. update typst_backend.rs if needed.",
+            EXPECTED_VERSION, ACTUAL_VERSION
+        );
+    }
+
+    #[test]
+    fn verify_compile_signature() {
+        // compile-time check: this won't compile if typst::compile signature changes
+        fn _assert_compile_signature<W: typst::World>(world: &W) {
+            let _: typst::diag::Warned<typst::diag::SourceResult<typst::layout::PagedDocument>> =
+                typst::compile::<typst::layout::PagedDocument>(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod vfs_tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_file_read_simple() {
+        let temp_dir = env::temp_dir().join("typst_vfs_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let test_file = temp_dir.join("data.txt");
+        fs::write(&test_file, b"Hello, VFS!").unwrap();
+
+        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+
+        world.update_source(
+            r#"#let data = read("data.txt")
+                          Data: #data"#,
+        );
+
+        let result = world.compile();
+
+        // Cleanup
+        fs::remove_dir_all(temp_dir).ok();
+
+        assert!(result.success, "Compilation should succeed")
+    }
+
+    #[test]
+    fn test_file_not_found() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        // Try to read non-existent file
+        world.update_source(r#"#read("doesnotexist.txt")"#);
+        let result = world.compile();
+
+        // Should fail
+        assert!(!result.success);
+        assert!(!result.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_import_statement() {
+        let temp_dir = env::temp_dir().join("typst_import_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let module_file = temp_dir.join("helper.typ");
+        fs::write(&module_file, b"#let greet(name) = \"Hello, \" + name").unwrap();
+
+        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+
+        // Compile with import
+        world.update_source(
+            r#"#import "helper.typ": greet
+                        #greet("World")"#,
+        );
+
+        let result = world.compile();
+
+        // Cleanup
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(result.success, "Import should work");
+    }
+
+    #[test]
+    fn test_read_json() {
+        let temp_dir = env::temp_dir().join("typst_json_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Create JSON file
+        let json_file = temp_dir.join("data.json");
+        fs::write(&json_file, br#"{"name": "Test", "value": 42}"#).unwrap();
+
+        // Create world
+        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+
+        // Read and parse JSON
+        world.update_source(
+            r#"#let data = json("data.json")
+                        Name: #data.name
+                        Value: #data.value"#,
+        );
+
+        let result = world.compile();
+
+        // Cleanup
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(result.success, "JSON read should work");
+    }
+
+    #[test]
+    fn test_nested_directory_import() {
+        let temp_dir = env::temp_dir().join("typst_nested_test");
+        fs::create_dir_all(temp_dir.join("components")).unwrap();
+
+        // Create nested file
+        let component = temp_dir.join("components/header.typ");
+        fs::write(&component, b"#let header = \"My Header\"").unwrap();
+
+        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+
+        // Import from subdirectory
+        world.update_source(
+            r#"#import "components/header.typ": header
+                       = #header"#,
+        );
+
+        let result = world.compile();
+
+        // Cleanup
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(result.success, "Nested import should work");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_backend_world_creation() {
+        let temp_dir = env::temp_dir();
+        let world = BackendWorld::new(temp_dir, None, None, vec![], true);
+
+        assert!(world.is_ok());
+    }
+
+    #[test]
+    fn test_backend_world_with_inputs() {
+        let temp_dir = env::temp_dir();
+        let inputs_json = r#"{"key": "value", "number": "42"}"#;
+        let world = BackendWorld::new(temp_dir, Some(inputs_json), None, vec![], true);
+        assert!(world.is_ok());
+    }
+
+    #[test]
+    fn test_set_inputs_replaces_sys_inputs() {
+        let temp_dir = env::temp_dir();
+        let mut world =
+            BackendWorld::new(temp_dir, Some(r#"{"name": "Alice"}"#), None, vec![], false).unwrap();
+        world.update_source(r#"#assert.eq(sys.inputs.name, "Alice")"#);
+        assert!(world.compile().success);
+
+        world.set_inputs(Some(r#"{"name": "Bob"}"#)).unwrap();
+        world.update_source(r#"#assert.eq(sys.inputs.name, "Bob")"#);
+        assert!(world.compile().success);
+
+        world.set_inputs(None).unwrap();
+        world.update_source(r#"#assert.eq(sys.inputs, (:))"#);
+        assert!(world.compile().success);
+
+        // Invalid JSON keeps the previous inputs
+        assert!(world.set_inputs(Some("{not json")).is_err());
+        assert!(world.compile().success);
+    }
+
+    #[test]
+    fn test_compile_batch_keeps_order_and_settings() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], false).unwrap();
+        world.set_limits(ResourceLimits {
+            max_pages: 1,
+            ..Default::default()
+        });
+        world.update_source(
+            "#for _ in range(sys.inputs.at(\"pages\", default: 1)) [x #pagebreak(weak: true)]",
+        );
+
+        let inputs: Vec<String> = (0..8).map(|_| r#"{"pages": 1}"#.to_string()).collect();
+        let batch = format!("[{}, null, {{\"pages\": 3}}]", inputs.join(", "));
+        let results = world.compile_batch(&batch, None).unwrap();
+
+        assert_eq!(results.len(), 10);
+        assert!(results[..9].iter().all(|result| result.success));
+        // Limits of the parent world apply to each entry
+        assert_eq!(results[9].aborted, Some(AbortReason::PageLimit));
+
+        assert!(world.compile_batch("{}", None).is_err());
+        assert!(world.compile_batch("[]", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parallel_render_keeps_page_order() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], false).unwrap();
+        world.set_render_threads(3).unwrap();
+        world.update_source("#for i in range(1, 8) [#i #h(i * 1cm) x #pagebreak(weak: true)]");
+
+        let doc = world.compile().document.unwrap();
+        assert_eq!(doc.page_count(), 7);
+
+        let svgs = doc.render_all_pages_svg(None).unwrap();
+        let pngs = doc.render_all_pages_png(0.5, None).unwrap();
+        assert_eq!(svgs.len(), 7);
+        assert_eq!(pngs.len(), 7);
+
+        for (index, (svg_page, png_page)) in svgs.iter().zip(&pngs).enumerate() {
+            assert_eq!(svg_page, &doc.render_page_svg(index).unwrap());
+            assert_eq!(png_page, &doc.render_page_png(index, 0.5).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_worlds_share_font_store() {
+        let temp_dir = env::temp_dir();
+        let fonts = FontStore::search(vec![], false, true);
+
+        let mut first = BackendWorld::with_fonts(temp_dir.clone(), None, vec![], &fonts).unwrap();
+        let second = BackendWorld::with_fonts(temp_dir, None, vec![], &fonts).unwrap();
+        assert!(Arc::ptr_eq(
+            &first.font_store.fonts,
+            &second.font_store.fonts
+        ));
+        assert!(Arc::ptr_eq(&first.font_store.book, &fonts.book));
+
+        // The store may go away before the worlds using it
+        drop(fonts);
+        first.update_source("= Shared fonts");
+        assert!(first.compile().success);
+    }
+
+    #[test]
+    fn test_backend_world_invalid_path() {
+        let invalid_path = PathBuf::from("/path/that/does/not/exist");
+        let world = BackendWorld::new(invalid_path, None, None, vec![], true);
+
+        assert!(world.is_err());
+        assert!(world.unwrap_err().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_backend_world_file_not_directory() {
+        // try to use a file as root (should fail)
+        let file_path = env::current_exe().unwrap();
+        let world = BackendWorld::new(file_path, None, None, vec![], true);
+
+        assert!(world.is_err());
+        assert!(world.unwrap_err().contains("not a directory"));
+    }
+
+    #[test]
+    fn test_simple_compilation_success() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        world.update_source("= Hello World\n\nThis is a test.");
+        let result = world.compile();
+
+        assert!(result.success);
+        assert!(result.document.is_some());
+
+        let doc = result.document.unwrap();
+        assert!(doc.page_count() > 0);
+    }
+
+    #[test]
+    fn test_compilation_error() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        // invalid syntax
+        world.update_source("#let x = (unclosed");
+        let result = world.compile();
+
+        assert!(!result.success);
+        assert!(result.document.is_none());
+        assert!(!result.diagnostics.is_empty());
+
+        // Should have at least one error
+        let has_error = result
+            .diagnostics
+            .iter()
+            .any(|d| matches!(d.severity, DiagnosticSeverity::Error));
+        assert!(has_error);
+    }
+
+    #[test]
+    fn test_svg_rendering_single_page() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        world.update_source("= Test page\n\nContent here.");
+        let result = world.compile();
+
+        assert!(result.success);
+        let doc = result.document.unwrap();
+
+        // Render fist page
+        let svg = doc.render_page_svg(0);
+        assert!(svg.is_ok());
+
+        let svg_bytes = svg.unwrap();
+        assert!(!svg_bytes.is_empty());
+
+        // Should be valid SVG (starts with XML declaration or <svg)
+        let svg_str = String::from_utf8_lossy(&svg_bytes);
+        assert!(svg_str.contains("<svg") || svg_str.starts_with("<?xml"));
+    }
+
+    #[test]
+    fn test_svg_rendering_all_pages() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        world.update_source("= Page 1\n#pagebreak()\n= Page 2\n#pagebreak()\n= Page 3");
+        let result = world.compile();
+
+        assert!(result.success);
+        let doc = result.document.unwrap();
+
+        // Render all pages
+        let svgs = doc.render_all_pages_svg(None);
+        assert!(svgs.is_ok());
+
+        let svg_pages = svgs.unwrap();
+        assert_eq!(svg_pages.len(), doc.page_count());
+
+        // Each page should be valid SVG
+        for svg_bytes in svg_pages {
+            assert!(!svg_bytes.is_empty());
+
+            let svg_str = String::from_utf8_lossy(&svg_bytes);
+            assert!(svg_str.contains("<svg") || svg_str.starts_with("<?xml"));
+        }
+    }
+
+    #[test]
+    fn test_diagnostic_formatting() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        // Introduce a warning (unused variable)
+        world.update_source("#let unused = 5\n= Title");
+        let result = world.compile();
+
+        // Should compile successfully but with a warning
+        assert!(result.success);
+
+        // Check diagnostic structure
+        for diag in &result.diagnostics {
+            assert!(!diag.message.is_empty());
+            // Severity should be valid
+            assert!(matches!(
+                diag.severity,
+                DiagnosticSeverity::Error | DiagnosticSeverity::Warning
+            ));
+        }
+    }
+
+    #[test]
+    fn test_update_source() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        // First compilation
+        world.update_source("= First");
+        let result1 = world.compile();
+        assert!(result1.success);
+
+        // Update source and recompile
+        world.update_source("= Second\nMore content.");
+        let result2 = world.compile();
+        assert!(result2.success);
+
+        // Both should succeed independently
+        assert!(result1.document.is_some());
+        assert!(result2.document.is_some());
+    }
+
+    #[test]
+    fn test_fonts_loaded() {
+        let temp_dir = env::temp_dir();
+        let world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        // Should have some fonts available
+        assert!(!world.font_store.fonts.fonts.is_empty());
+
+        // Should be able to get a font
+        let font = world.font(0);
+        assert!(font.is_some());
+    }
+
+    #[test]
+    fn test_without_embedded_fonts() {
+        let fonts = FontStore::search(vec![], false, false);
+        assert!(fonts.entries().is_empty());
+
+        let mut world = BackendWorld::with_fonts(env::temp_dir(), None, vec![], &fonts).unwrap();
+        world.update_source("No fonts");
+        // Text without fonts is skipped, but the document still compiles
+        assert!(world.compile().success);
+    }
+
+    #[test]
+    #[cfg(feature = "embed-fonts")]
+    fn test_font_report() {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        world.update_source(
+            "Hello\n#pagebreak()\n#text(font: \"DejaVu Sans Mono\")[code] 漢字\n#pagebreak()\n漢",
+        );
+
+        let doc = world.compile().document.unwrap();
+        let report = doc.font_report();
+
+        let families: Vec<&str> = report.fonts.iter().map(|f| f.family.as_str()).collect();
+        assert_eq!(families[0], "Libertinus Serif");
+        assert!(families.contains(&"DejaVu Sans Mono"));
+        assert_eq!(report.fonts[0].pages, vec![0, 1, 2]);
+
+        // No bundled font covers CJK
+        let missing: Vec<&str> = report
+            .missing_glyphs
+            .iter()
+            .map(|g| g.text.as_str())
+            .collect();
+        assert_eq!(missing, vec!["漢", "字"]);
+        assert_eq!(report.missing_glyphs[0].pages, vec![1, 2]);
+        assert_eq!(report.missing_glyphs[1].pages, vec![1]);
+    }
+
+    #[test]
+    #[cfg(feature = "embed-fonts")]
+    fn test_list_fonts() {
+        let font_dir = env::temp_dir().join("typst_list_fonts_test");
+        fs::create_dir_all(&font_dir).unwrap();
+
+        let mut world =
+            BackendWorld::new(env::temp_dir(), None, None, vec![font_dir.clone()], false).unwrap();
+        let embedded = world.list_fonts();
+        fs::remove_dir_all(&font_dir).ok();
+
+        // Without system fonts, only the bundled fonts are loaded
+        assert!(!embedded.is_empty());
+        assert!(embedded.iter().all(|f| f.source == FontSource::Embedded));
+
+        let mono = embedded
+            .iter()
+            .find(|f| f.family == "DejaVu Sans Mono" && f.weight == 400 && f.style == "normal")
+            .expect("embedded DejaVu Sans Mono");
+        assert_eq!(mono.stretch, 1.0);
+        // Basic Latin letters are covered
+        assert!(
+            mono.coverage
+                .iter()
+                .any(|&(start, end)| start <= 'A' as u32 && 'z' as u32 <= end)
+        );
+
+        let data = world.font(0).unwrap().data().to_vec();
+        world.add_font(data);
+        let fonts = world.list_fonts();
+        assert_eq!(fonts.len(), embedded.len() + 1);
+        assert_eq!(fonts.last().unwrap().source, FontSource::Memory);
+        assert_eq!(fonts.last().unwrap().family, fonts[0].family);
+    }
+
+    #[test]
+    #[cfg(feature = "embed-fonts")]
+    fn test_world_without_system_fonts() {
+        let temp_dir = env::temp_dir();
+        let world = BackendWorld::new(temp_dir, None, None, vec![], false);
+
+        // Should still succeed (embedded fonts available)
+        assert!(world.is_ok());
+
+        let world = world.unwrap();
+        // Should still have embedded fonts
+        assert!(!world.font_store.fonts.fonts.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod pdf_tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_pdf_rendering_basic() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        world.update_source("= PDF Test\n\nContent here.");
+        let result = world.compile();
+
+        assert!(result.success);
+        let doc = result.document.unwrap();
+
+        let pdf_bytes = doc.render_pdf(None).unwrap();
+        assert!(!pdf_bytes.is_empty());
+
+        // PDF should start with %PDF-
+        assert_eq!(&pdf_bytes[0..5], b"%PDF-");
+    }
+
+    #[test]
+    fn test_pdf_multipage() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+
+        world.update_source("= Page 1\n#pagebreak()\n= Page 2");
+        let result = world.compile();
+
+        assert!(result.success);
+        let doc = result.document.unwrap();
+
+        let pdf_bytes = doc.render_pdf(None).unwrap();
+        assert!(pdf_bytes.len() > 1000); // Should be larger than 1KB
+    }
+}
+
+#[cfg(test)]
+mod package_tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_package_import_with_path() {
+        // Mock package structure
+        let temp_dir = env::temp_dir().join("typst_package_test");
+        let package_dir = temp_dir.join("packages");
+        let preview_dir = package_dir.join("preview");
+        let pkg_name_dir = preview_dir.join("mylib");
+        let pkg_version_dir = pkg_name_dir.join("0.1.0");
+
+        fs::create_dir_all(&pkg_version_dir).unwrap();
+
+        let toml_file = pkg_version_dir.join("typst.toml");
+        let toml_content = r#"
+            [package]
+            name = "mylib"
+            version = "0.1.0"
+            entrypoint = "lib.typ"
+        "#;
+
+        fs::write(&toml_file, toml_content).unwrap();
+
+        let lib_file = pkg_version_dir.join("lib.typ");
+        fs::write(&lib_file, b"#let hello = \"Hello from package!\"").unwrap();
+
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
+
+        world.update_source(
+            r#"#import "@preview/mylib:0.1.0": hello
+                                             #hello"#,
+        );
+
+        let result = world.compile();
+
+        if !result.success {
+            for diag in &result.diagnostics {
+                println!("Error: {}", diag.message);
+            }
+        }
+
+        // Cleanup
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(result.success, "Package import should work");
+    }
+
+    /// Builds a `.tar.gz` archive from `(path, contents)` pairs
+    fn tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            // `set_path` rejects `..`, write the raw name to test traversal entries
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_package_from_archive() {
+        let temp_dir = env::temp_dir().join("typst_archive_package_test");
+        let package_dir = temp_dir.join("packages");
+        let preview_dir = package_dir.join("preview");
+        fs::create_dir_all(&preview_dir).unwrap();
+
+        let archive = tar_gz(&[
+            (
+                "typst.toml",
+                b"[package]\nname = \"hello\"\nversion = \"0.1.0\"\nentrypoint = \"src/lib.typ\"\n",
+            ),
+            ("./src/lib.typ", b"#let greet = \"Hello from archive!\""),
+            ("../escape.typ", b"#let escaped = true"),
+        ]);
+        fs::write(preview_dir.join("hello-0.1.0.tar.gz"), archive).unwrap();
+
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
+
+        world.update_source(
+            r#"#import "@preview/hello:0.1.0": greet
+#greet"#,
+        );
+        let imported = world.compile();
+
+        let unpacked = PackageArchive::open(&preview_dir.join("hello-0.1.0.tar.gz"), 0).unwrap();
+        let too_large = PackageArchive::open(&preview_dir.join("hello-0.1.0.tar.gz"), 16);
+
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(imported.success, "Package should load from the archive");
+        assert!(unpacked.files.contains_key(Path::new("src/lib.typ")));
+        assert_eq!(unpacked.files.len(), 2, "Traversal entries must be skipped");
+        assert!(too_large.is_err());
+    }
+
+    #[test]
+    fn test_package_archive_reloaded_when_replaced() {
+        let temp_dir = env::temp_dir().join("typst_archive_replaced_test");
+        let preview_dir = temp_dir.join("packages/preview");
+        let workspace = temp_dir.join("workspace");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&preview_dir).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let manifest: &[u8] =
+            b"[package]\nname = \"hello\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n";
+        let archive_path = preview_dir.join("hello-0.1.0.tar.gz");
+        fs::write(
+            &archive_path,
+            tar_gz(&[("typst.toml", manifest), ("lib.typ", b"#let greet = 1")]),
+        )
+        .unwrap();
+
+        let mut world = BackendWorld::new(
+            workspace,
+            None,
+            Some(temp_dir.join("packages")),
+            vec![],
+            false,
+        )
+        .unwrap();
+        world.update_source("#import \"@preview/hello:0.1.0\": greet\n#greet");
+        let original = world.compile();
+        // Forks share the cache, so they must see the new archive too
+        let mut fork = world.snapshot("#import \"@preview/hello:0.1.0\": greet\n#greet");
+
+        fs::write(
+            &archive_path,
+            tar_gz(&[("typst.toml", manifest), ("lib.typ", b"#let welcome = 2")]),
+        )
+        .unwrap();
+        let replaced = world.compile();
+        let forked = fork.compile();
+
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(original.success);
+        assert!(!replaced.success, "Replaced archive should be read again");
+        assert!(!forked.success);
+    }
+
+    #[test]
+    fn test_archive_cache_evicts_least_recently_used() {
+        let spec = |name: &str| {
+            format!("@preview/{}:0.1.0", name)
+                .parse::<PackageSpec>()
+                .unwrap()
+        };
+        let (a, b, c) = (spec("a"), spec("b"), spec("c"));
+        let mut cache = ArchiveCache::new(100);
+
+        let slot = cache.slot(&a);
+        cache.loaded(&a, &slot, 40);
+        let slot = cache.slot(&b);
+        cache.loaded(&b, &slot, 40);
+        // Using `a` again makes `b` the least recently used
+        cache.slot(&a);
+        let slot = cache.slot(&c);
+        cache.loaded(&c, &slot, 40);
+
+        assert!(cache.entries.contains_key(&a));
+        assert!(!cache.entries.contains_key(&b));
+        assert!(cache.entries.contains_key(&c));
+        assert_eq!(cache.bytes, 80);
+
+        // An archive larger than the cache evicts everything else
+        let slot = cache.slot(&b);
+        cache.loaded(&b, &slot, 150);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.bytes, 150);
+
+        // A slot evicted while its archive loads is not accounted for
+        let stale = cache.slot(&a);
+        cache.entries.remove(&a);
+        cache.loaded(&a, &stale, 40);
+        assert_eq!(cache.bytes, 150);
+    }
+
+    #[test]
+    fn test_package_archive_entry_cap() {
+        // Header of a file too large to expand, without its (gigantic) data
+        let mut header = tar::Header::new_gnu();
+        header.set_path("bomb.typ").unwrap();
+        header.set_size(MAX_ARCHIVE_ENTRY_BYTES + 1);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, header.as_bytes()).unwrap();
+        let archive = encoder.finish().unwrap();
+
+        // Even without a sandbox limit
+        let error = PackageArchive::read(archive.as_slice(), "bomb", Path::new("bomb"), 0)
+            .err()
+            .unwrap();
+        assert!(error.contains("bomb.typ is larger than"), "{}", error);
+    }
+
+    /// Serves `archive` as `/preview/hello-0.1.0.tar.gz` over HTTP, 404 for anything else
+    ///
+    /// Returns the registry URL and a counter of the requests served.
+    fn serve_registry(archive: Vec<u8>) -> (String, Arc<AtomicUsize>) {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).unwrap();
+                // Drain the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let response = if request_line.starts_with("GET /preview/hello-0.1.0.tar.gz ") {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        archive.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(&archive);
+                    response
+                } else {
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                };
+                stream.write_all(&response).ok();
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[test]
+    fn test_package_download_from_registry() {
+        let temp_dir = env::temp_dir().join("typst_registry_package_test");
+        let cache_dir = temp_dir.join("cache");
+        let workspace = temp_dir.join("workspace");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let (url, requests) = serve_registry(tar_gz(&[
+            (
+                "typst.toml",
+                b"[package]\nname = \"hello\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n",
+            ),
+            ("lib.typ", b"#let greet = \"Hello from the registry!\""),
+        ]));
+        let registry = |offline| PackageRegistry {
+            url: url.clone(),
+            cache_path: cache_dir.clone(),
+            offline,
+        };
+        let compile = |registry: PackageRegistry, source: &str| {
+            let mut world = BackendWorld::new(workspace.clone(), None, None, vec![], true).unwrap();
+            world.set_package_registry(Some(registry)).unwrap();
+            world.update_source(source);
+            world.compile()
+        };
+
+        let downloaded = compile(
+            registry(false),
+            "#import \"@preview/hello:0.1.0\": greet\n#greet",
+        );
+        let cached = cache_dir
+            .join("preview")
+            .join("hello-0.1.0.tar.gz")
+            .is_file();
+        let offline = compile(
+            registry(true),
+            "#import \"@preview/hello:0.1.0\": greet\n#greet",
+        );
+        let offline_missing = compile(registry(true), "#import \"@preview/other:0.1.0\": *");
+        let missing = compile(registry(false), "#import \"@preview/other:0.1.0\": *");
+
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(downloaded.success, "Package should be downloaded");
+        assert!(cached, "Downloaded archive should be cached");
+        assert!(offline.success, "Cached package should work offline");
+        assert!(!offline_missing.success);
+        assert!(!missing.success);
+        assert!(
+            missing.diagnostics[0].message.contains("not found"),
+            "{}",
+            missing.diagnostics[0].message
+        );
+        // One download and one 404, the offline compiles never hit the network
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_package_download_interrupted() {
+        let cache_dir = env::temp_dir().join("typst_registry_interrupt_test");
+        fs::remove_dir_all(&cache_dir).ok();
+
+        let (url, _) = serve_registry(tar_gz(&[(
+            "typst.toml",
+            b"[package]\nname = \"hello\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n",
+        )]));
+        let registry = PackageRegistry {
+            url,
+            cache_path: cache_dir.clone(),
+            offline: false,
+        };
+        let spec: PackageSpec = "@preview/hello:0.1.0".parse().unwrap();
+
+        let result = registry.download(&spec, 0, || {
+            Err(FileError::Other(Some("compilation was cancelled".into())))
+        });
+        let cached = cache_dir.join("preview").is_dir();
+        fs::remove_dir_all(&cache_dir).ok();
+
+        assert!(matches!(result, Err(FileError::Other(_))));
+        assert!(!cached, "An interrupted download must not be cached");
+    }
+
+    #[test]
+    fn test_package_roots_in_order() {
+        let temp_dir = env::temp_dir().join("typst_package_roots_test");
+        let vendored = temp_dir.join("vendored");
+        let shared = temp_dir.join("shared");
+        let workspace = temp_dir.join("workspace");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let write_package = |root: &Path, spec: &str, lib: &str| {
+            let dir = root.join(spec);
+            fs::create_dir_all(&dir).unwrap();
+            let mut parts = spec.split('/');
+            let (name, version) = (parts.nth(1).unwrap(), parts.next().unwrap());
+            fs::write(
+                dir.join("typst.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"{}\"\nentrypoint = \"lib.typ\"\n",
+                    name, version
+                ),
+            )
+            .unwrap();
+            fs::write(dir.join("lib.typ"), lib).unwrap();
+        };
+        write_package(
+            &vendored,
+            "preview/mylib/0.1.0",
+            "#let origin = \"patched\"",
+        );
+        write_package(&shared, "preview/mylib/0.1.0", "#let origin = \"registry\"");
+        write_package(&shared, "local/tool/1.0.0", "#let tool = \"local\"");
+
+        let (url, requests) = serve_registry(Vec::new());
+        let fonts = FontStore::search(vec![], false, true);
+        let mut world =
+            BackendWorld::with_fonts(workspace, None, vec![vendored, shared], &fonts).unwrap();
+        world
+            .set_package_registry(Some(PackageRegistry {
+                url,
+                cache_path: temp_dir.join("cache"),
+                offline: false,
+            }))
+            .unwrap();
+
+        world.update_source(
+            r#"#import "@preview/mylib:0.1.0": origin
+#import "@local/tool:1.0.0": tool
+#assert.eq(origin, "patched")
+#assert.eq(tool, "local")"#,
+        );
+        let result = world.compile();
+
+        world.update_source(r#"#import "@local/missing:0.1.0": *"#);
+        let missing_local = world.compile();
+
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(
+            result.success,
+            "{:?}",
+            result
+                .diagnostics
+                .iter()
+                .map(|d| &d.message)
+                .collect::<Vec<_>>()
+        );
+        assert!(!missing_local.success);
+        assert_eq!(
+            requests.load(Ordering::SeqCst),
+            0,
+            "@local packages must not be downloaded"
+        );
+    }
+
+    #[test]
+    fn test_package_manifest_validation() {
+        let temp_dir = env::temp_dir().join("typst_package_manifest_test");
+        let package_dir = temp_dir.join("packages");
+        let workspace = temp_dir.join("workspace");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let write_package = |name: &str, manifest: Option<&str>| {
+            let dir = package_dir.join("preview").join(name).join("0.1.0");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("lib.typ"), "#let x = 1").unwrap();
+            if let Some(manifest) = manifest {
+                fs::write(dir.join("typst.toml"), manifest).unwrap();
+            }
+        };
+        let manifest = |name: &str, extra: &str| {
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n{}",
+                name, extra
+            )
+        };
+        write_package("renamed", Some(&manifest("original", "")));
+        write_package(
+            "no-entry",
+            Some(&manifest("no-entry", "").replace("lib.typ", "main.typ")),
+        );
+        write_package("no-manifest", None);
+        write_package(
+            "future",
+            Some(&manifest("future", "compiler = \"99.0.0\"\n")),
+        );
+        write_package("broken", Some("[package"));
+
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir), vec![], false).unwrap();
+        let mut error_for = |name: &str| {
+            world.update_source(&format!("#import \"@preview/{}:0.1.0\": *", name));
+            let result = world.compile();
+            assert!(!result.success, "{} should fail", name);
+            result.diagnostics[0].message.clone()
+        };
+
+        let renamed = error_for("renamed");
+        let no_entry = error_for("no-entry");
+        let no_manifest = error_for("no-manifest");
+        let future = error_for("future");
+        let broken = error_for("broken");
+
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(renamed.contains("@preview/renamed:0.1.0"), "{}", renamed);
+        assert!(renamed.contains("mismatched name"), "{}", renamed);
+        assert!(
+            no_entry.contains("entrypoint `main.typ` does not exist"),
+            "{}",
+            no_entry
+        );
+        assert!(
+            no_manifest.contains("no typst.toml manifest"),
+            "{}",
+            no_manifest
+        );
+        assert!(future.contains("requires Typst 99.0.0"), "{}", future);
+        assert!(broken.contains("@preview/broken:0.1.0"), "{}", broken);
+        assert!(broken.contains("malformed"), "{}", broken);
+    }
+
+    #[test]
+    fn test_package_manifest_revalidated() {
+        let temp_dir = env::temp_dir().join("typst_package_revalidate_test");
+        let package_dir = temp_dir.join("packages");
+        let dir = package_dir.join("preview/edited/0.1.0");
+        let workspace = temp_dir.join("workspace");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let manifest =
+            "[package]\nname = \"edited\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n";
+        fs::write(dir.join("typst.toml"), manifest).unwrap();
+        fs::write(dir.join("lib.typ"), "#let x = 1").unwrap();
+
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir), vec![], false).unwrap();
+        world.update_source("#import \"@preview/edited:0.1.0\": x\n#x");
+        let valid = world.compile();
+
+        fs::write(
+            dir.join("typst.toml"),
+            manifest.replace("lib.typ", "main.typ"),
+        )
+        .unwrap();
+        let edited = world.compile();
+
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(valid.success);
+        assert!(!edited.success, "Edited manifest should be validated again");
+        assert!(
+            edited.diagnostics[0]
+                .message
+                .contains("entrypoint `main.typ` does not exist"),
+            "{}",
+            edited.diagnostics[0].message
+        );
+    }
+
+    #[test]
+    fn test_document_package_inventory() {
+        let temp_dir = env::temp_dir().join("typst_package_inventory_test");
+        let package_dir = temp_dir.join("packages");
+        let unpacked_dir = package_dir.join("preview/unpacked/1.0.0");
+        let workspace = temp_dir.join("workspace");
+        fs::remove_dir_all(&temp_dir).ok();
+        fs::create_dir_all(&unpacked_dir).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+
+        fs::write(
+            unpacked_dir.join("typst.toml"),
+            "[package]\nname = \"unpacked\"\nversion = \"1.0.0\"\nentrypoint = \"lib.typ\"\n",
+        )
+        .unwrap();
+        fs::write(
+            unpacked_dir.join("lib.typ"),
+            "#import \"@preview/hello:0.1.0\": greet\n#let both = greet",
+        )
+        .unwrap();
+        fs::write(
+            package_dir.join("preview/hello-0.1.0.tar.gz"),
+            tar_gz(&[
+                (
+                    "typst.toml",
+                    b"[package]\nname = \"hello\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n",
+                ),
+                ("lib.typ", b"#let greet = \"hi\""),
+            ]),
+        )
+        .unwrap();
+
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], false).unwrap();
+        world.update_source("#import \"@preview/unpacked:1.0.0\": both\n#both");
+        let with_packages = world.compile();
+        let dependencies = world.dependencies();
+
+        world.update_source("No packages");
+        let without_packages = world.compile();
+
+        let package_dir = package_dir.canonicalize().unwrap();
+        fs::remove_dir_all(&temp_dir).ok();
+
+        let packages = with_packages.document.unwrap().packages;
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "unpacked");
+        assert_eq!(packages[0].path, package_dir.join("preview/unpacked/1.0.0"));
+        assert!(!packages[0].archive);
+        assert_eq!(
+            (packages[1].namespace.as_str(), packages[1].version.as_str()),
+            ("preview", "0.1.0")
+        );
+        assert_eq!(
+            packages[1].path,
+            package_dir.join("preview/hello-0.1.0.tar.gz")
+        );
+        assert!(packages[1].archive);
+
+        assert_eq!(
+            dependencies,
+            vec![
+                package_dir.join("preview/unpacked/1.0.0/typst.toml"),
+                package_dir.join("preview/unpacked/1.0.0/lib.typ"),
+                package_dir.join("preview/hello-0.1.0.tar.gz"),
+            ]
+        );
+
+        assert!(without_packages.document.unwrap().packages.is_empty());
+    }
+
+    #[test]
+    fn test_package_not_found() {
+        let temp_dir = env::temp_dir();
+        let package_dir = temp_dir.join("empty_packages");
+        fs::create_dir_all(&package_dir).unwrap();
+
+        let mut world = BackendWorld::new(
+            temp_dir.clone(),
+            None,
+            Some(package_dir.clone()),
+            vec![],
+            true,
+        )
+        .unwrap();
+
+        // Try to import non-existent package
+        world.update_source(r#"#import "@preview/doesnotexist:0.1.0": *"#);
+        let result = world.compile();
+
+        // Cleanup
+        fs::remove_dir_all(&package_dir).ok();
+
+        // Should fail
+        assert!(!result.success);
+        assert!(!result.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_package_without_path_configured() {
+        let temp_dir = env::temp_dir();
+        let mut world = BackendWorld::new(
+            temp_dir,
+            None,
+            None, // No package path
+            vec![],
+            true,
+        )
+        .unwrap();
+
+        // Try to import package without path configured
+        world.update_source(r#"#import "@preview/test:0.1.0": *"#);
+        let result = world.compile();
+
+        // Should fail with meaningful error
+        assert!(!result.success);
+        assert!(!result.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_package_nested_files() {
+        let temp_dir = env::temp_dir().join("typst_nested_package_test");
+        let package_dir = temp_dir.join("packages");
+        let pkg_base_dir = package_dir.join("preview").join("utils");
+        let pkg_version_dir = pkg_base_dir.join("1.0.0");
+        let src_dir = pkg_version_dir.join("src");
+
+        fs::create_dir_all(&src_dir).unwrap();
+        fs::create_dir_all(&pkg_version_dir).unwrap();
+
+        let toml_file = pkg_version_dir.join("typst.toml");
+        let toml_content = r#"
+            [package]
+            name = "utils"
+            version = "1.0.0"
+            entrypoint = "lib.typ"
+        "#;
+
+        fs::write(&toml_file, toml_content).unwrap();
+
+        // Create nested files
+        fs::write(
+            pkg_version_dir.join("lib.typ"),
+            b"#import \"src/helpers.typ\": *",
+        )
+        .unwrap();
 
-        // Create JSON file
-        let json_file = temp_dir.join("data.json");
-        fs::write(&json_file, br#"{"name": "Test", "value": 42}"#).unwrap();
+        fs::write(
+            src_dir.join("helpers.typ"),
+            b"#let greet = \"Hello from nested file!\"",
+        )
+        .unwrap();
 
-        // Create world
-        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
 
-        // Read and parse JSON
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
+
+        // Import package that imports nested file
         world.update_source(
-            r#"#let data = json("data.json")
-                        Name: #data.name
-                        Value: #data.value"#,
+            r#"#import "@preview/utils:1.0.0": greet
+                        #greet"#,
         );
 
         let result = world.compile();
@@ -568,496 +3469,704 @@ mod vfs_tests {
         // Cleanup
         fs::remove_dir_all(&temp_dir).ok();
 
-        assert!(result.success, "JSON read should work");
+        assert!(result.success, "Nested package imports should work");
     }
 
     #[test]
-    fn test_nested_directory_import() {
-        let temp_dir = env::temp_dir().join("typst_nested_test");
-        fs::create_dir_all(&temp_dir.join("components")).unwrap();
+    fn test_package_path_security() {
+        let temp_dir = env::temp_dir().join("typst_security_test");
+        let package_dir = temp_dir.join("packages");
+        fs::create_dir_all(&package_dir).unwrap();
 
-        // Create nested file
-        let component = temp_dir.join("components/header.typ");
-        fs::write(&component, b"#let header = \"My Header\"").unwrap();
+        // Create a file OUTSIDE package directory
+        let secret_file = temp_dir.join("secret.typ");
+        fs::write(&secret_file, b"#let secret = \"LEAKED!\"").unwrap();
 
-        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], true).unwrap();
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
 
-        // Import from subdirectory
-        world.update_source(
-            r#"#import "components/header.typ": header
-                       = #header"#,
-        );
+        let mut world =
+            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
 
+        // Try path traversal attack
+        world.update_source(r#"#import "@preview/../secret:0.0.0": *"#);
         let result = world.compile();
 
         // Cleanup
         fs::remove_dir_all(&temp_dir).ok();
 
-        assert!(result.success, "Nested import should work");
+        // Should fail - cannot escape package directory
+        assert!(!result.success);
     }
 }
 
 #[cfg(test)]
-mod tests {
+mod font_tests {
     use super::*;
     use std::env;
 
     #[test]
-    fn test_backend_world_creation() {
-        let temp_dir = env::temp_dir();
-        let world = BackendWorld::new(temp_dir, None, None, vec![], true);
+    fn test_custom_font_paths_no_crash() {
+        let temp_dir = env::temp_dir().join("typst_font_test");
+        fs::create_dir_all(&temp_dir).unwrap();
 
-        assert!(world.is_ok());
-    }
+        let world = BackendWorld::new(
+            env::current_dir().unwrap(),
+            None,
+            None,
+            vec![temp_dir.clone()],
+            false, // Only use custom paths, not system fonts
+        );
 
-    #[test]
-    fn test_backend_world_with_inputs() {
-        let temp_dir = env::temp_dir();
-        let inputs_json = r#"{"key": "value", "number": "42"}"#;
-        let world = BackendWorld::new(temp_dir, Some(inputs_json), None, vec![], true);
-        assert!(world.is_ok());
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(world.is_ok(), "Should create world with custom font path");
     }
 
     #[test]
-    fn test_backend_world_invalid_path() {
-        let invalid_path = PathBuf::from("/path/that/does/not/exist");
-        let world = BackendWorld::new(invalid_path, None, None, vec![], true);
+    #[cfg(feature = "embed-fonts")]
+    fn test_add_font_from_memory() {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        let searched = world.font_store.fonts.fonts.len();
+        let shared = world.font_store.clone();
 
-        assert!(world.is_err());
-        assert!(world.unwrap_err().contains("does not exist"));
-    }
+        let data = world.font(0).unwrap().data().to_vec();
+        let family = world.font(0).unwrap().info().family.clone();
 
-    #[test]
-    fn test_backend_world_file_not_directory() {
-        // try to use a file as root (should fail)
-        let file_path = env::current_exe().unwrap();
-        let world = BackendWorld::new(file_path, None, None, vec![], true);
+        assert_eq!(world.add_font(data), 1);
+        assert!(world.book().info(searched).is_some());
+        assert_eq!(world.font(searched).unwrap().info().family, family);
 
-        assert!(world.is_err());
-        assert!(world.unwrap_err().contains("not a directory"));
+        // Holders of the original store are unaffected
+        assert!(shared.font(searched).is_none());
+        assert!(shared.book.info(searched).is_none());
+
+        assert_eq!(world.add_font(b"not a font".to_vec()), 0);
+        assert!(world.font(searched + 1).is_none());
     }
 
     #[test]
-    fn test_simple_compilation_success() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_multiple_font_paths() {
+        let temp1 = env::temp_dir().join("fonts1");
+        let temp2 = env::temp_dir().join("fonts2");
+        fs::create_dir_all(&temp1).unwrap();
+        fs::create_dir_all(&temp2).unwrap();
 
-        world.update_source("= Hello World\n\nThis is a test.");
-        let result = world.compile();
+        let world = BackendWorld::new(
+            env::current_dir().unwrap(),
+            None,
+            None,
+            vec![temp1.clone(), temp2.clone()],
+            true,
+        );
 
-        assert!(result.success);
-        assert!(result.document.is_some());
+        fs::remove_dir_all(&temp1).ok();
+        fs::remove_dir_all(&temp2).ok();
 
-        let doc = result.document.unwrap();
-        assert!(doc.page_count() > 0);
+        assert!(world.is_ok());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod symlink_tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::symlink;
+
+    fn workspace_id(path: &str) -> FileId {
+        FileId::new(None, VirtualPath::new(path))
     }
 
     #[test]
-    fn test_compilation_error() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_symlinked_file_escaping_root_denied() {
+        let temp_dir = env::temp_dir().join("typst_symlink_file_test");
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
 
-        // invalid syntax
-        world.update_source("#let x = (unclosed");
+        let secret_file = temp_dir.join("secret.txt");
+        fs::write(&secret_file, b"top secret").unwrap();
+        symlink(&secret_file, workspace.join("link.txt")).unwrap();
+
+        let mut world = BackendWorld::new(workspace, None, None, vec![], false).unwrap();
+        let resolved = world.resolve_path(workspace_id("link.txt"));
+
+        world.update_source(r#"#read("link.txt")"#);
         let result = world.compile();
 
-        assert!(!result.success);
-        assert!(result.document.is_none());
-        assert!(!result.diagnostics.is_empty());
+        fs::remove_dir_all(&temp_dir).ok();
 
-        // Should have at least one error
-        let has_error = result
-            .diagnostics
-            .iter()
-            .any(|d| matches!(d.severity, DiagnosticSeverity::Error));
-        assert!(has_error);
+        assert!(matches!(resolved, Err(FileError::AccessDenied)));
+        assert!(
+            !result.success,
+            "Symlink escaping the root must not be readable"
+        );
     }
 
     #[test]
-    fn test_svg_rendering_single_page() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
-
-        world.update_source("= Test page\n\nContent here.");
-        let result = world.compile();
+    fn test_symlinked_directory_escaping_root_denied() {
+        let temp_dir = env::temp_dir().join("typst_symlink_dir_test");
+        let workspace = temp_dir.join("workspace");
+        let outside = temp_dir.join("outside");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&outside).unwrap();
 
-        assert!(result.success);
-        let doc = result.document.unwrap();
+        fs::write(outside.join("secret.typ"), b"#let secret = \"LEAKED!\"").unwrap();
+        symlink(&outside, workspace.join("shared")).unwrap();
 
-        // Render fist page
-        let svg = doc.render_page_svg(0);
-        assert!(svg.is_ok());
+        let world = BackendWorld::new(workspace, None, None, vec![], false).unwrap();
+        let resolved = world.resolve_path(workspace_id("shared/secret.typ"));
 
-        let svg_bytes = svg.unwrap();
-        assert!(!svg_bytes.is_empty());
+        fs::remove_dir_all(&temp_dir).ok();
 
-        // Should be valid SVG (starts with XML declaration or <svg)
-        let svg_str = String::from_utf8_lossy(&svg_bytes);
-        assert!(svg_str.contains("<svg") || svg_str.starts_with("<?xml"));
+        assert!(matches!(resolved, Err(FileError::AccessDenied)));
     }
 
     #[test]
-    fn test_svg_rendering_all_pages() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
-
-        world.update_source("= Page 1\n#pagebreak()\n= Page 2\n#pagebreak()\n= Page 3");
-        let result = world.compile();
-
-        assert!(result.success);
-        let doc = result.document.unwrap();
+    fn test_symlink_inside_root_allowed() {
+        let temp_dir = env::temp_dir().join("typst_symlink_inside_test");
+        fs::create_dir_all(temp_dir.join("data")).unwrap();
 
-        // Render all pages
-        let svgs = doc.render_all_pages_svg();
-        assert!(svgs.is_ok());
+        fs::write(temp_dir.join("data/real.txt"), b"hello").unwrap();
+        symlink(temp_dir.join("data/real.txt"), temp_dir.join("alias.txt")).unwrap();
 
-        let svg_pages = svgs.unwrap();
-        assert_eq!(svg_pages.len(), doc.page_count());
+        let mut world = BackendWorld::new(temp_dir.clone(), None, None, vec![], false).unwrap();
+        world.update_source(r#"#read("alias.txt")"#);
+        let result = world.compile();
 
-        // Each page should be valid SVG
-        for svg_bytes in svg_pages {
-            assert!(!svg_bytes.is_empty());
+        fs::remove_dir_all(&temp_dir).ok();
 
-            let svg_str = String::from_utf8_lossy(&svg_bytes);
-            assert!(svg_str.contains("<svg") || svg_str.starts_with("<?xml"));
-        }
+        assert!(
+            result.success,
+            "Symlinks that stay inside the root should work"
+        );
     }
 
     #[test]
-    fn test_diagnostic_formatting() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_symlinked_root_allowed() {
+        let temp_dir = env::temp_dir().join("typst_symlink_root_test");
+        let real_root = temp_dir.join("real");
+        fs::create_dir_all(&real_root).unwrap();
+        fs::write(real_root.join("data.txt"), b"hello").unwrap();
 
-        // Introduce a warning (unused variable)
-        world.update_source("#let unused = 5\n= Title");
+        let linked_root = temp_dir.join("linked");
+        symlink(&real_root, &linked_root).unwrap();
+
+        let mut world = BackendWorld::new(linked_root, None, None, vec![], false).unwrap();
+        world.update_source(r#"#read("data.txt")"#);
         let result = world.compile();
 
-        // Should compile successfully but with a warning
-        assert!(result.success);
+        fs::remove_dir_all(&temp_dir).ok();
 
-        // Check diagnostic structure
-        for diag in &result.diagnostics {
-            assert!(!diag.message.is_empty());
-            // Severity should be valid
-            assert!(matches!(
-                diag.severity,
-                DiagnosticSeverity::Error | DiagnosticSeverity::Warning
-            ));
-        }
+        assert!(
+            result.success,
+            "A symlinked root should resolve to its target"
+        );
     }
 
     #[test]
-    fn test_update_source() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_parent_traversal_denied() {
+        let temp_dir = env::temp_dir().join("typst_traversal_test");
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::write(temp_dir.join("secret.txt"), b"top secret").unwrap();
 
-        // First compilation
-        world.update_source("= First");
-        let result1 = world.compile();
-        assert!(result1.success);
+        let world = BackendWorld::new(workspace, None, None, vec![], false).unwrap();
+        let resolved = world.resolve_path(workspace_id("../secret.txt"));
 
-        // Update source and recompile
-        world.update_source("= Second\nMore content.");
-        let result2 = world.compile();
-        assert!(result2.success);
+        fs::remove_dir_all(&temp_dir).ok();
 
-        // Both should succeed independently
-        assert!(result1.document.is_some());
-        assert!(result2.document.is_some());
+        assert!(matches!(resolved, Err(FileError::AccessDenied)));
     }
 
     #[test]
-    fn test_fonts_loaded() {
-        let temp_dir = env::temp_dir();
-        let world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_package_symlink_escaping_package_path_denied() {
+        let temp_dir = env::temp_dir().join("typst_symlink_package_test");
+        let package_dir = temp_dir.join("packages");
+        let pkg_version_dir = package_dir.join("preview/evil/0.1.0");
+        let workspace = temp_dir.join("workspace");
+        fs::create_dir_all(&pkg_version_dir).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
 
-        // Should have some fonts available
-        assert!(!world.fonts.fonts.is_empty());
+        let secret_file = temp_dir.join("secret.typ");
+        fs::write(&secret_file, b"#let secret = \"LEAKED!\"").unwrap();
+        symlink(&secret_file, pkg_version_dir.join("lib.typ")).unwrap();
 
-        // Should be able to get a font
-        let font = world.font(0);
-        assert!(font.is_some());
-    }
+        let world = BackendWorld::new(workspace, None, Some(package_dir), vec![], false).unwrap();
 
-    #[test]
-    fn test_world_without_system_fonts() {
-        let temp_dir = env::temp_dir();
-        let world = BackendWorld::new(temp_dir, None, None, vec![], false);
+        let spec: typst::syntax::package::PackageSpec = "@preview/evil:0.1.0".parse().unwrap();
+        let id = FileId::new(Some(spec), VirtualPath::new("lib.typ"));
+        let resolved = world.resolve_path(id);
 
-        // Should still succeed (embedded fonts available)
-        assert!(world.is_ok());
+        fs::remove_dir_all(&temp_dir).ok();
 
-        let world = world.unwrap();
-        // Should still have embedded fonts
-        assert!(!world.fonts.fonts.is_empty());
+        assert!(matches!(resolved, Err(FileError::AccessDenied)));
     }
 }
 
 #[cfg(test)]
-mod pdf_tests {
+mod sandbox_tests {
     use super::*;
     use std::env;
 
+    fn sandboxed_world(root: PathBuf, sandbox: SandboxPolicy) -> BackendWorld {
+        let mut world = BackendWorld::new(root, None, None, vec![], false).unwrap();
+        world.set_sandbox(sandbox);
+        world
+    }
+
     #[test]
-    fn test_pdf_rendering_basic() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_forbid_filesystem() {
+        let temp_dir = env::temp_dir().join("typst_sandbox_forbid_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("data.txt"), b"hello").unwrap();
 
-        world.update_source("= PDF Test\n\nContent here.");
-        let result = world.compile();
+        let mut world = sandboxed_world(
+            temp_dir.clone(),
+            SandboxPolicy {
+                forbid_filesystem: true,
+                ..Default::default()
+            },
+        );
 
-        assert!(result.success);
-        let doc = result.document.unwrap();
+        world.update_source(r#"#read("data.txt")"#);
+        let result = world.compile();
 
-        let pdf_bytes = doc.render_pdf().unwrap();
-        assert!(!pdf_bytes.is_empty());
+        fs::remove_dir_all(&temp_dir).ok();
 
-        // PDF should start with %PDF-
-        assert_eq!(&pdf_bytes[0..5], b"%PDF-");
+        assert!(!result.success);
+        assert!(
+            result.diagnostics[0]
+                .message
+                .contains("filesystem access is disabled")
+        );
     }
 
     #[test]
-    fn test_pdf_multipage() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(temp_dir, None, None, vec![], true).unwrap();
+    fn test_forbid_filesystem_allows_main_source() {
+        let mut world = sandboxed_world(
+            env::temp_dir(),
+            SandboxPolicy {
+                forbid_filesystem: true,
+                ..Default::default()
+            },
+        );
 
-        world.update_source("= Page 1\n#pagebreak()\n= Page 2");
+        world.update_source("= Only the main source");
         let result = world.compile();
 
         assert!(result.success);
-        let doc = result.document.unwrap();
-
-        let pdf_bytes = doc.render_pdf().unwrap();
-        assert!(pdf_bytes.len() > 1000); // Should be larger than 1KB
     }
-}
-
-#[cfg(test)]
-mod package_tests {
-    use super::*;
-    use std::env;
-    use std::fs;
 
     #[test]
-    fn test_package_import_with_path() {
-        // Mock package structure
-        let temp_dir = env::temp_dir().join("typst_package_test");
-        let package_dir = temp_dir.join("packages");
-        let preview_dir = package_dir.join("preview");
-        let pkg_name_dir = preview_dir.join("mylib");
-        let pkg_version_dir = pkg_name_dir.join("0.1.0");
+    fn test_allowed_extensions() {
+        let temp_dir = env::temp_dir().join("typst_sandbox_extension_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("data.json"), br#"{"a": 1}"#).unwrap();
+        fs::write(temp_dir.join("data.txt"), b"hello").unwrap();
 
-        fs::create_dir_all(&pkg_version_dir).unwrap();
+        let mut world = sandboxed_world(
+            temp_dir.clone(),
+            SandboxPolicy {
+                allowed_extensions: vec!["json".to_string()],
+                ..Default::default()
+            },
+        );
 
-        let toml_file = pkg_version_dir.join("typst.toml");
-        let toml_content = r#"
-            [package]
-            name = "mylib"
-            version = "0.1.0"
-            entrypoint = "lib.typ"
-        "#;
+        world.update_source(r#"#json("data.json")"#);
+        let allowed = world.compile();
 
-        fs::write(&toml_file, toml_content).unwrap();
+        world.update_source(r#"#read("data.txt")"#);
+        let denied = world.compile();
 
-        let lib_file = pkg_version_dir.join("lib.typ");
-        fs::write(&lib_file, b"#let hello = \"Hello from package!\"").unwrap();
+        fs::remove_dir_all(&temp_dir).ok();
 
-        let workspace = temp_dir.join("workspace");
-        fs::create_dir_all(&workspace).unwrap();
+        assert!(allowed.success, "Allowed extension should be readable");
+        assert!(!denied.success);
+        assert!(denied.diagnostics[0].message.contains(".txt"));
+    }
 
-        let mut world =
-            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
+    #[test]
+    fn test_max_file_size() {
+        let temp_dir = env::temp_dir().join("typst_sandbox_file_size_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("small.txt"), b"ok").unwrap();
+        fs::write(temp_dir.join("large.txt"), vec![b'x'; 1024]).unwrap();
 
-        world.update_source(
-            r#"#import "@preview/mylib:0.1.0": hello
-                                             #hello"#,
+        let mut world = sandboxed_world(
+            temp_dir.clone(),
+            SandboxPolicy {
+                max_file_size: 100,
+                ..Default::default()
+            },
         );
 
-        let result = world.compile();
+        world.update_source(r#"#read("small.txt")"#);
+        let small = world.compile();
 
-        if !result.success {
-            for diag in &result.diagnostics {
-                println!("Error: {}", diag.message);
-            }
-        }
+        world.update_source(r#"#read("large.txt")"#);
+        let large = world.compile();
 
-        // Cleanup
         fs::remove_dir_all(&temp_dir).ok();
 
-        assert!(result.success, "Package import should work");
+        assert!(small.success);
+        assert!(!large.success);
+        assert!(
+            large.diagnostics[0]
+                .message
+                .contains("exceeds the sandbox limit of 100 bytes")
+        );
     }
 
     #[test]
-    fn test_package_not_found() {
-        let temp_dir = env::temp_dir();
-        let package_dir = temp_dir.join("empty_packages");
-        fs::create_dir_all(&package_dir).unwrap();
+    fn test_max_total_bytes() {
+        let temp_dir = env::temp_dir().join("typst_sandbox_total_test");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("a.txt"), vec![b'a'; 60]).unwrap();
+        fs::write(temp_dir.join("b.txt"), vec![b'b'; 60]).unwrap();
 
-        let mut world = BackendWorld::new(
+        let mut world = sandboxed_world(
             temp_dir.clone(),
-            None,
-            Some(package_dir.clone()),
-            vec![],
-            true,
-        )
-        .unwrap();
+            SandboxPolicy {
+                max_total_bytes: 100,
+                ..Default::default()
+            },
+        );
 
-        // Try to import non-existent package
-        world.update_source(r#"#import "@preview/doesnotexist:0.1.0": *"#);
-        let result = world.compile();
+        world.update_source(r#"#read("a.txt")"#);
+        let single = world.compile();
 
-        // Cleanup
-        fs::remove_dir_all(&package_dir).ok();
+        // Counter is reset per compilation, so the same file works again,
+        // but reading both in one compilation exceeds the budget
+        world.update_source(r#"#read("a.txt") #read("b.txt")"#);
+        let both = world.compile();
 
-        // Should fail
-        assert!(!result.success);
-        assert!(!result.diagnostics.is_empty());
+        fs::remove_dir_all(&temp_dir).ok();
+
+        assert!(single.success);
+        assert!(!both.success);
+        assert!(both.diagnostics[0].message.contains("total bytes read"));
     }
 
     #[test]
-    fn test_package_without_path_configured() {
-        let temp_dir = env::temp_dir();
-        let mut world = BackendWorld::new(
-            temp_dir,
-            None,
-            None, // No package path
-            vec![],
-            true,
-        )
-        .unwrap();
+    fn test_read_budget_reserved_atomically() {
+        let world = sandboxed_world(
+            env::temp_dir(),
+            SandboxPolicy {
+                max_total_bytes: 100,
+                ..Default::default()
+            },
+        );
 
-        // Try to import package without path configured
-        world.update_source(r#"#import "@preview/test:0.1.0": *"#);
+        // Only one of many concurrent 60 byte reads fits the budget
+        let reserved = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| world.reserve_read_budget(60).is_ok()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|&reserved| reserved)
+                .count()
+        });
+
+        assert_eq!(reserved, 1);
+        assert_eq!(world.bytes_read.load(Ordering::Relaxed), 60);
+    }
+}
+
+#[cfg(test)]
+mod limit_tests {
+    use super::*;
+    use std::env;
+
+    fn limited_world(limits: ResourceLimits) -> BackendWorld {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        world.set_limits(limits);
+        world
+    }
+
+    #[test]
+    fn test_time_limit_exceeded() {
+        let mut world = limited_world(ResourceLimits {
+            max_compile_time_ms: 1,
+            ..Default::default()
+        });
+
+        world.update_source(
+            "#let total = 0\n#for i in range(50000) { total += calc.rem(i, 7) }\n#total",
+        );
         let result = world.compile();
 
-        // Should fail with meaningful error
         assert!(!result.success);
-        assert!(!result.diagnostics.is_empty());
+        assert!(result.document.is_none());
+        assert_eq!(result.aborted, Some(AbortReason::TimeLimit));
+        assert!(result.diagnostics[0].message.contains("time limit"));
     }
 
     #[test]
-    fn test_package_nested_files() {
-        let temp_dir = env::temp_dir().join("typst_nested_package_test");
-        let package_dir = temp_dir.join("packages");
-        let pkg_base_dir = package_dir.join("preview").join("utils");
-        let pkg_version_dir = pkg_base_dir.join("1.0.0");
-        let src_dir = pkg_version_dir.join("src");
+    fn test_time_limit_stops_compute_only_compile() {
+        let mut world = limited_world(ResourceLimits {
+            max_compile_time_ms: 100,
+            ..Default::default()
+        });
+
+        // Never touches a file, so only the watchdog can stop it
+        world.update_source("#for i in range(5000000) {}");
+        let started = Instant::now();
+        let result = world.compile();
 
-        fs::create_dir_all(&src_dir).unwrap();
-        fs::create_dir_all(&pkg_version_dir).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(result.aborted, Some(AbortReason::TimeLimit));
+        // Still running on its pool worker
+        assert!(crate::compile_pool::abandoned_jobs() >= 1);
 
-        let toml_file = pkg_version_dir.join("typst.toml");
-        let toml_content = r#"
-            [package]
-            name = "utils"
-            version = "1.0.0"
-            entrypoint = "lib.typ"
-        "#;
+        // The world stays usable
+        world.update_source("= Quick");
+        assert!(world.compile().success);
+    }
 
-        fs::write(&toml_file, toml_content).unwrap();
+    #[test]
+    fn test_time_limit_applies_to_each_batch_entry() {
+        let mut world = limited_world(ResourceLimits {
+            max_compile_time_ms: 300,
+            ..Default::default()
+        });
+
+        world.update_source("#for i in range(sys.inputs.at(\"n\", default: 0)) {}\nDone");
+        let batch = r#"[null, {"n": 5000000}, {"n": 10}]"#;
+        let started = Instant::now();
+        let results = world.compile_batch(batch, None).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(results[0].success);
+        assert_eq!(results[1].aborted, Some(AbortReason::TimeLimit));
+        assert!(results[2].success);
+
+        // The abandoned entry would slow down other timed tests
+        let drained = Instant::now() + Duration::from_secs(10);
+        while crate::compile_pool::abandoned_jobs() > 0 && Instant::now() < drained {
+            std::thread::sleep(Duration::from_millis(10));
+        }
 
-        // Create nested files
-        fs::write(
-            pkg_version_dir.join("lib.typ"),
-            b"#import \"src/helpers.typ\": *",
-        )
-        .unwrap();
+        // A triggered token stops every entry
+        let token = Arc::new(CancellationToken::default());
+        token.cancel();
+        let results = world.compile_batch(batch, Some(token)).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(
+            results
+                .iter()
+                .all(|result| result.aborted == Some(AbortReason::Cancelled))
+        );
+    }
 
-        fs::write(
-            src_dir.join("helpers.typ"),
-            b"#let greet = \"Hello from nested file!\"",
-        )
-        .unwrap();
+    #[test]
+    fn test_page_limit_exceeded() {
+        let mut world = limited_world(ResourceLimits {
+            max_pages: 2,
+            ..Default::default()
+        });
 
-        let workspace = temp_dir.join("workspace");
-        fs::create_dir_all(&workspace).unwrap();
+        world.update_source("= Page 1\n#pagebreak()\n= Page 2");
+        let within = world.compile();
 
-        let mut world =
-            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
+        world.update_source("= Page 1\n#pagebreak()\n= Page 2\n#pagebreak()\n= Page 3");
+        let exceeded = world.compile();
 
-        // Import package that imports nested file
-        world.update_source(
-            r#"#import "@preview/utils:1.0.0": greet
-                        #greet"#,
-        );
+        assert!(within.success);
+        assert!(within.aborted.is_none());
+
+        assert!(!exceeded.success);
+        assert!(exceeded.document.is_none());
+        assert_eq!(exceeded.aborted, Some(AbortReason::PageLimit));
+        assert!(exceeded.diagnostics[0].message.contains("3 pages"));
+    }
+
+    #[test]
+    fn test_output_limit_exceeded() {
+        let mut world = limited_world(ResourceLimits {
+            max_output_bytes: 100,
+            ..Default::default()
+        });
 
+        world.update_source("= Output Limit\n\nThis renders to more than 100 bytes.");
         let result = world.compile();
+        assert!(result.success);
 
-        // Cleanup
-        fs::remove_dir_all(&temp_dir).ok();
+        let doc = result.document.unwrap();
 
-        assert!(result.success, "Nested package imports should work");
+        assert!(matches!(
+            doc.render_page_svg(0),
+            Err(RenderError::OutputLimitExceeded { limit: 100, .. })
+        ));
+        assert!(matches!(
+            doc.render_all_pages_svg(None),
+            Err(RenderError::OutputLimitExceeded { .. })
+        ));
+        assert!(matches!(
+            doc.render_pdf(None),
+            Err(RenderError::OutputLimitExceeded { .. })
+        ));
     }
 
     #[test]
-    fn test_package_path_security() {
-        let temp_dir = env::temp_dir().join("typst_security_test");
-        let package_dir = temp_dir.join("packages");
-        fs::create_dir_all(&package_dir).unwrap();
+    fn test_no_limits_by_default() {
+        let mut world = limited_world(ResourceLimits::default());
 
-        // Create a file OUTSIDE package directory
-        let secret_file = temp_dir.join("secret.typ");
-        fs::write(&secret_file, b"#let secret = \"LEAKED!\"").unwrap();
+        world.update_source("= Page 1\n#pagebreak()\n= Page 2");
+        let result = world.compile();
 
-        let workspace = temp_dir.join("workspace");
-        fs::create_dir_all(&workspace).unwrap();
+        assert!(result.success);
+        assert!(result.document.unwrap().render_pdf(None).is_ok());
+    }
+}
 
-        let mut world =
-            BackendWorld::new(workspace, None, Some(package_dir.clone()), vec![], true).unwrap();
+#[cfg(test)]
+mod cancel_tests {
+    use super::*;
+    use std::env;
+    use std::thread;
+    use std::time::Duration;
 
-        // Try path traversal attack
-        world.update_source(r#"#import "@preview/../secret:0.0.0": *"#);
-        let result = world.compile();
+    #[test]
+    fn test_precancelled_compile() {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        world.update_source("= Never compiled");
 
-        // Cleanup
-        fs::remove_dir_all(&temp_dir).ok();
+        let token = Arc::new(CancellationToken::default());
+        token.cancel();
+
+        let result = world.compile_with_cancel(token);
+
+        assert!(!result.success);
+        assert!(result.document.is_none());
+        assert_eq!(result.aborted, Some(AbortReason::Cancelled));
+    }
+
+    #[test]
+    fn test_cancel_from_other_thread() {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        // Pure computation, no file access to notice the token at
+        world.update_source("#for i in range(5000000) {}");
+
+        let token = Arc::new(CancellationToken::default());
+        let canceller = {
+            let token = token.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(5));
+                token.cancel();
+            })
+        };
+
+        let started = Instant::now();
+        let result = world.compile_with_cancel(token);
+        canceller.join().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // The world is reusable after a cancelled compilation
+        world.update_source("= After cancel");
+        let after = world.compile();
 
-        // Should fail - cannot escape package directory
         assert!(!result.success);
+        assert_eq!(result.aborted, Some(AbortReason::Cancelled));
+        assert!(after.success);
+    }
+
+    #[test]
+    fn test_cancelled_render() {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        world.update_source("= Page 1\n#pagebreak()\n= Page 2");
+        let doc = world.compile().document.unwrap();
+
+        let token = CancellationToken::default();
+        assert!(doc.render_all_pages_svg(Some(&token)).is_ok());
+
+        token.cancel();
+        assert!(matches!(
+            doc.render_all_pages_svg(Some(&token)),
+            Err(RenderError::Cancelled)
+        ));
+        assert!(matches!(
+            doc.render_pdf(Some(&token)),
+            Err(RenderError::Cancelled)
+        ));
     }
 }
 
 #[cfg(test)]
-mod font_tests {
+mod date_tests {
     use super::*;
     use std::env;
 
+    // 2023-11-14 22:13:20 UTC
+    const FIXED_DATE: i64 = 1_700_000_000;
+
+    fn fixed_world() -> BackendWorld {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        world.set_fixed_date(Some(FIXED_DATE)).unwrap();
+        world
+    }
+
     #[test]
-    fn test_custom_font_paths_no_crash() {
-        let temp_dir = env::temp_dir().join("typst_font_test");
-        fs::create_dir_all(&temp_dir).unwrap();
+    fn test_fixed_today() {
+        let world = fixed_world();
 
-        let world = BackendWorld::new(
-            env::current_dir().unwrap(),
-            None,
-            None,
-            vec![temp_dir.clone()],
-            false, // Only use custom paths, not system fonts
+        assert_eq!(
+            world.today(None),
+            Datetime::from_ymd_hms(2023, 11, 14, 22, 13, 20)
         );
+        assert_eq!(
+            world.today(Some(2)),
+            Datetime::from_ymd_hms(2023, 11, 15, 0, 13, 20)
+        );
+    }
 
-        fs::remove_dir_all(&temp_dir).ok();
+    #[test]
+    fn test_fixed_today_in_document() {
+        let mut world = fixed_world();
 
-        assert!(world.is_ok(), "Should create world with custom font path");
+        world.update_source(
+            r#"#assert.eq(datetime.today().display("[year]-[month]-[day]"), "2023-11-14")
+               #datetime.today().display()"#,
+        );
+        let result = world.compile();
+
+        assert!(result.success, "today() should return the fixed date");
     }
 
     #[test]
-    fn test_multiple_font_paths() {
-        let temp1 = env::temp_dir().join("fonts1");
-        let temp2 = env::temp_dir().join("fonts2");
-        fs::create_dir_all(&temp1).unwrap();
-        fs::create_dir_all(&temp2).unwrap();
+    fn test_reproducible_pdf() {
+        let mut world = fixed_world();
+        let source = "= Reproducible\n\nToday is #datetime.today().display().";
+
+        world.update_source(source);
+        let first = world.compile().document.unwrap().render_pdf(None).unwrap();
+
+        let mut other_world = fixed_world();
+        other_world.update_source(source);
+        let second = other_world
+            .compile()
+            .document
+            .unwrap()
+            .render_pdf(None)
+            .unwrap();
 
-        let world = BackendWorld::new(
-            env::current_dir().unwrap(),
-            None,
-            None,
-            vec![temp1.clone(), temp2.clone()],
-            true,
+        assert_eq!(
+            first, second,
+            "Identical inputs should produce identical PDFs"
         );
 
-        fs::remove_dir_all(&temp1).ok();
-        fs::remove_dir_all(&temp2).ok();
+        let pdf_text = String::from_utf8_lossy(&first);
+        assert!(
+            pdf_text.contains("20231114221320"),
+            "PDF should carry the fixed creation date"
+        );
+    }
 
-        assert!(world.is_ok());
+    #[test]
+    fn test_invalid_fixed_date() {
+        let mut world = BackendWorld::new(env::temp_dir(), None, None, vec![], false).unwrap();
+        assert!(world.set_fixed_date(Some(i64::MAX)).is_err());
+        assert!(world.set_fixed_date(None).is_ok());
     }
 }
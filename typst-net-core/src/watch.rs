@@ -0,0 +1,448 @@
+// Watch mode: recompile a source whenever a file it depends on changes.
+// Uses filesystem notifications, results are delivered through a C callback.
+
+use crate::cancel::CancellationToken;
+use crate::compiler::CompilerInstance;
+use crate::types::CompileResult;
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Receives every compile result of a watch session
+///
+/// The callee owns `result` and must free it with `typst_net_result_free`.
+pub type WatchCallback = unsafe extern "C" fn(result: CompileResult, user_data: *mut c_void);
+
+/// Quiet period after a change before recompiling, so a burst of writes
+/// (an editor save, a git checkout) triggers a single compilation
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Message for the watch thread
+enum Message {
+    Changed(notify::Result<Event>),
+    Stop,
+}
+
+/// Where compile results go
+struct Delivery {
+    callback: WatchCallback,
+    user_data: *mut c_void,
+}
+
+// SAFETY: the host guarantees that the callback and its user data may be used
+// from the watch thread (see `WatchSession::start`)
+unsafe impl Send for Delivery {}
+
+impl Delivery {
+    fn deliver(&self, result: CompileResult) {
+        unsafe { (self.callback)(result, self.user_data) }
+    }
+}
+
+/// Directories observed for changes
+struct WatchedPaths {
+    watcher: RecommendedWatcher,
+    /// Workspace root, watched recursively
+    root: PathBuf,
+    /// Directories of package files the last compilation used, watched non-recursively
+    package_dirs: HashSet<PathBuf>,
+}
+
+impl WatchedPaths {
+    /// Watch the directories of the package files among `dependencies`, and
+    /// stop watching those the compilation no longer uses
+    ///
+    /// Package roots are not watched as a whole: the user data directory and
+    /// download cache hold every package, which could exhaust inotify watches.
+    fn update(&mut self, dependencies: &HashSet<PathBuf>) {
+        let wanted: HashSet<&Path> = dependencies
+            .iter()
+            .filter(|path| !path.starts_with(&self.root))
+            .filter_map(|path| path.parent())
+            .collect();
+
+        let watcher = &mut self.watcher;
+        self.package_dirs.retain(|dir| {
+            let keep = wanted.contains(dir.as_path());
+            if !keep {
+                let _ = watcher.unwatch(dir);
+            }
+            keep
+        });
+
+        for dir in wanted {
+            // Directories of missing files may not exist and can't be watched
+            if !self.package_dirs.contains(dir)
+                && watcher.watch(dir, RecursiveMode::NonRecursive).is_ok()
+            {
+                self.package_dirs.insert(dir.to_path_buf());
+            }
+        }
+    }
+}
+
+/// A running watch, stopped when dropped
+pub struct WatchSession {
+    stop: Sender<Message>,
+    /// Cancels the compilation in progress when the session stops
+    cancel_token: Arc<CancellationToken>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchSession {
+    /// Compile `source` now and again whenever a file it requested changes
+    ///
+    /// Watches the workspace root and the package directories the last
+    /// compilation used. While the last compilation failed, any change under
+    /// them triggers a recompilation, as the change may create a file that
+    /// was missing.
+    ///
+    /// # Safety
+    /// - `compiler` must stay alive until the session is dropped
+    /// - `callback` and `user_data` must be safe to use from another thread
+    pub unsafe fn start(
+        compiler: *const CompilerInstance,
+        source: String,
+        callback: WatchCallback,
+        user_data: *mut c_void,
+    ) -> Result<Self, String> {
+        let root = unsafe { &*compiler }.root();
+
+        let (sender, receiver) = mpsc::channel();
+        let events = sender.clone();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = events.send(Message::Changed(event));
+        })
+        .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+        let mut watched = WatchedPaths {
+            watcher,
+            root,
+            package_dirs: HashSet::new(),
+        };
+
+        let delivery = Delivery {
+            callback,
+            user_data,
+        };
+        let cancel_token = Arc::new(CancellationToken::default());
+        let compile_cancel_token = Arc::clone(&cancel_token);
+        // Raw pointers aren't Send; the caller keeps the compiler alive
+        let compiler = compiler as usize;
+        let thread = thread::Builder::new()
+            .name("typst-net-watch".into())
+            .spawn(move || {
+                let compiler = unsafe { &*(compiler as *const CompilerInstance) };
+                watch_loop(
+                    compiler,
+                    &source,
+                    &receiver,
+                    &delivery,
+                    &mut watched,
+                    &compile_cancel_token,
+                );
+            })
+            .map_err(|e| format!("Failed to start watch thread: {}", e))?;
+
+        Ok(Self {
+            stop: sender,
+            cancel_token,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for WatchSession {
+    /// Stop watching, cancelling a running compilation, and wait for its
+    /// result to be delivered
+    ///
+    /// Cancelled compilations return right away, so this never waits for a
+    /// long compilation to finish.
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+        let _ = self.stop.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Compile, then recompile after every relevant change until stopped
+fn watch_loop(
+    compiler: &CompilerInstance,
+    source: &str,
+    receiver: &Receiver<Message>,
+    delivery: &Delivery,
+    watched: &mut WatchedPaths,
+    cancel_token: &Arc<CancellationToken>,
+) {
+    let (mut dependencies, mut failed) = compile(compiler, source, delivery, watched, cancel_token);
+
+    loop {
+        let event = match receiver.recv() {
+            Ok(Message::Changed(event)) => event,
+            Ok(Message::Stop) | Err(_) => return,
+        };
+
+        if !is_relevant(event, &dependencies, failed) {
+            continue;
+        }
+
+        // Wait until changes settle
+        loop {
+            match receiver.recv_timeout(DEBOUNCE) {
+                Ok(Message::Changed(_)) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        (dependencies, failed) = compile(compiler, source, delivery, watched, cancel_token);
+    }
+}
+
+/// Compile and deliver the result, returning its dependencies and whether it failed
+///
+/// The package directories are watched before the result is delivered, so a
+/// change the callback makes right away is not missed.
+fn compile(
+    compiler: &CompilerInstance,
+    source: &str,
+    delivery: &Delivery,
+    watched: &mut WatchedPaths,
+    cancel_token: &Arc<CancellationToken>,
+) -> (HashSet<PathBuf>, bool) {
+    let (result, dependencies) = compiler.compile_tracked(source, Some(Arc::clone(cancel_token)));
+    let dependencies = dependencies.into_iter().collect();
+    watched.update(&dependencies);
+
+    let failed = !result.success;
+    delivery.deliver(result);
+    (dependencies, failed)
+}
+
+/// Whether a filesystem event can change the compilation output
+fn is_relevant(
+    event: notify::Result<Event>,
+    dependencies: &HashSet<PathBuf>,
+    failed: bool,
+) -> bool {
+    let Ok(event) = event else {
+        // Events may have been lost, recompile to be safe
+        return true;
+    };
+
+    // Reads (including our own) don't change anything
+    let read_only = match event.kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => false,
+        kind => kind.is_access(),
+    };
+    if read_only {
+        return false;
+    }
+
+    failed || event.paths.iter().any(|path| dependencies.contains(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CompilerOptions;
+    use std::env;
+    use std::fs;
+
+    unsafe extern "C" fn send_success(result: CompileResult, user_data: *mut c_void) {
+        let sender = unsafe { &*(user_data as *const Sender<bool>) };
+        let _ = sender.send(result.success);
+        unsafe { crate::typst_net_result_free(result) };
+    }
+
+    #[test]
+    fn test_watch_recompiles_on_change() {
+        let root = env::temp_dir().join("typst_watch_test");
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("chapter.typ"), "= Chapter").unwrap();
+
+        let compiler = CompilerInstance::new(root.clone(), &CompilerOptions::default()).unwrap();
+        let (sender, results) = mpsc::channel::<bool>();
+        let timeout = Duration::from_secs(10);
+
+        let session = unsafe {
+            WatchSession::start(
+                &compiler,
+                "#include \"chapter.typ\"".to_string(),
+                send_success,
+                &sender as *const Sender<bool> as *mut c_void,
+            )
+        }
+        .unwrap();
+
+        // Initial compilation
+        assert!(results.recv_timeout(timeout).unwrap());
+
+        // Unrelated files are ignored while the last compile succeeded
+        fs::write(root.join("notes.txt"), "unrelated").unwrap();
+        assert!(results.recv_timeout(Duration::from_millis(300)).is_err());
+
+        // Breaking a dependency recompiles
+        fs::write(root.join("chapter.typ"), "#undefined-variable").unwrap();
+        assert!(!results.recv_timeout(timeout).unwrap());
+
+        // Fixing it recompiles again
+        fs::write(root.join("chapter.typ"), "= Fixed").unwrap();
+        assert!(results.recv_timeout(timeout).unwrap());
+
+        drop(session);
+        fs::write(root.join("chapter.typ"), "= After stop").unwrap();
+        assert!(results.recv_timeout(Duration::from_millis(300)).is_err());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_watch_stop_cancels_compile() {
+        let root = env::temp_dir().join("typst_watch_stop_test");
+        fs::create_dir_all(&root).unwrap();
+
+        let compiler = CompilerInstance::new(root.clone(), &CompilerOptions::default()).unwrap();
+        let (sender, results) = mpsc::channel::<bool>();
+
+        let session = unsafe {
+            WatchSession::start(
+                &compiler,
+                // Unlike the limit tests' loop, so it can't be memoized already
+                "#for i in range(5000000) { i }".to_string(),
+                send_success,
+                &sender as *const Sender<bool> as *mut c_void,
+            )
+        }
+        .unwrap();
+
+        std::thread::sleep(Duration::from_millis(200));
+        let started = std::time::Instant::now();
+        drop(session);
+        let elapsed = started.elapsed();
+
+        fs::remove_dir_all(&root).ok();
+
+        assert!(
+            elapsed < Duration::from_secs(2),
+            "Stopping took {:?}",
+            elapsed
+        );
+        // The cancelled compilation is still delivered
+        assert_eq!(results.try_recv(), Ok(false));
+    }
+
+    /// Builds a `.tar.gz` package archive from `(path, contents)` pairs
+    fn tar_gz(files: &[(&str, &str)]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_path(path).unwrap();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, data.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_watch_recompiles_on_archive_change() {
+        let root = env::temp_dir().join("typst_watch_archive_test");
+        let packages = root.join("packages");
+        let workspace = root.join("workspace");
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(packages.join("preview")).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let manifest =
+            "[package]\nname = \"hello\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"\n";
+        let archive = packages.join("preview/hello-0.1.0.tar.gz");
+        let write_archive = |lib: &str| {
+            fs::write(
+                &archive,
+                tar_gz(&[("typst.toml", manifest), ("lib.typ", lib)]),
+            )
+            .unwrap();
+        };
+        write_archive("#let greet = \"Hello\"");
+
+        let package_path = packages.to_str().unwrap();
+        let options = CompilerOptions {
+            package_path: package_path.as_ptr(),
+            package_path_len: package_path.len(),
+            ..CompilerOptions::default()
+        };
+        let compiler = CompilerInstance::new(workspace, &options).unwrap();
+        let (sender, results) = mpsc::channel::<bool>();
+        let timeout = Duration::from_secs(10);
+
+        let session = unsafe {
+            WatchSession::start(
+                &compiler,
+                "#import \"@preview/hello:0.1.0\": greet\n#greet".to_string(),
+                send_success,
+                &sender as *const Sender<bool> as *mut c_void,
+            )
+        }
+        .unwrap();
+
+        assert!(results.recv_timeout(timeout).unwrap());
+
+        // The cached archive must not hide the new contents
+        write_archive("#let welcome = \"Hello\"");
+        assert!(!results.recv_timeout(timeout).unwrap());
+
+        write_archive("#let greet = \"Hello again\"");
+        assert!(results.recv_timeout(timeout).unwrap());
+
+        drop(session);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_watch_package_dirs_follow_dependencies() {
+        let root = env::temp_dir().join("typst_watch_package_dirs_test");
+        let packages = root.join("packages");
+        let workspace = root.join("workspace");
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(packages.join("preview/a/0.1.0")).unwrap();
+        fs::create_dir_all(packages.join("preview/b/0.1.0")).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+
+        let mut watched = WatchedPaths {
+            watcher: notify::recommended_watcher(|_| {}).unwrap(),
+            root: workspace.clone(),
+            package_dirs: HashSet::new(),
+        };
+        let dependencies = |paths: &[PathBuf]| paths.iter().cloned().collect::<HashSet<_>>();
+
+        watched.update(&dependencies(&[
+            workspace.join("chapter.typ"),
+            packages.join("preview/a/0.1.0/lib.typ"),
+            packages.join("preview/missing/0.1.0/lib.typ"),
+        ]));
+        let first = watched.package_dirs.clone();
+
+        watched.update(&dependencies(&[packages.join("preview/b/0.1.0/lib.typ")]));
+        let second = watched.package_dirs.clone();
+
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(first, dependencies(&[packages.join("preview/a/0.1.0")]));
+        assert_eq!(second, dependencies(&[packages.join("preview/b/0.1.0")]));
+    }
+}